@@ -1,6 +1,6 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use hmac_sha256::Hash;
-use merkle::merkle::MerkleTree;
+use merkle::merkle::{MerkleTree, MerkleTreeCache, Proof};
 
 fn bench_create_merkle_tree(c: &mut Criterion) {
     c.bench_function("create merkle tree", |b| b.iter(|| {
@@ -33,11 +33,11 @@ fn bench_generate_proof(c: &mut Criterion) {
 
         let i = contents.len() / 2;
 
-        let hash = hashes[i].clone();
+        let hash = hashes[i];
 
         let mtree = MerkleTree::new(hashes);
 
-        let proofs = mtree.generate_proofs(hash).unwrap();
+        let proofs = mtree.generate_proofs(hash).unwrap().into_tuples();
         let expected_root = mtree.root_hash();
 
         let root = MerkleTree::verify(contents[i].as_bytes().to_vec(), proofs);
@@ -46,9 +46,81 @@ fn bench_generate_proof(c: &mut Criterion) {
     }));
 }
 
+fn bench_verify_long_proofs(c: &mut Criterion) {
+    // proofs as deep as a 2^24 leaf tree, the siblings don't need to come from a real tree
+    let proofs: Vec<(Vec<u8>, Proof)> = (0..100_000u32).map(|i| {
+        let data = i.to_le_bytes().to_vec();
+        let proof = (0..24u32).map(|level| {
+            (Hash::hash(&[i.to_le_bytes(), level.to_le_bytes()].concat()), ((i >> level) & 1) as u8)
+        }).collect();
+        (data, proof)
+    }).collect();
+
+    let mut group = c.benchmark_group("verify");
+    group.sample_size(10);
+    // `verify` takes its arguments by value, the clones are made outside of the timed part
+    group.bench_function("verify 100k 24-step proofs", |b| b.iter_batched(
+        || proofs.clone(),
+        |proofs| {
+            for (data, proof) in proofs {
+                black_box(MerkleTree::verify(data, proof));
+            }
+        },
+        BatchSize::LargeInput,
+    ));
+
+    let leaf_hashes: Vec<[u8; 32]> = proofs.iter().map(|(data, _)| Hash::hash(data)).collect();
+    let root = [0u8; 32];
+    group.bench_function("verify_zerocopy 100k 24-step proofs", |b| b.iter(|| {
+        for (leaf_hash, (_, proof)) in leaf_hashes.iter().zip(&proofs) {
+            black_box(MerkleTree::verify_zerocopy(leaf_hash, proof, &root));
+        }
+    }));
+    group.finish();
+}
+
+fn bench_proofs_large_tree(c: &mut Criterion) {
+    let hashes: Vec<[u8; 32]> = (0..100_000u32).map(|i| Hash::hash(&i.to_le_bytes())).collect();
+    let mtree = MerkleTree::new(hashes.clone());
+
+    // leaves spread over the whole tree so the lookup can't get lucky
+    let targets: Vec<[u8; 32]> = hashes.iter().step_by(1000).copied().collect();
+
+    c.bench_function("generate 100 proofs in a 100k leaf tree", |b| b.iter(|| {
+        for hash in &targets {
+            black_box(mtree.generate_proofs(*hash).unwrap());
+        }
+    }));
+}
+
+fn bench_rebuild_cached(c: &mut Criterion) {
+    let hashes: Vec<[u8; 32]> = (0..10_000u32).map(|i| Hash::hash(&i.to_le_bytes())).collect();
+    // the tree being rebuilt differs from the cached one in a single leaf
+    let mut changed = hashes.clone();
+    changed[5000] = Hash::hash(b"changed");
+
+    let mut group = c.benchmark_group("rebuild 10k leaf tree with one leaf changed");
+    group.bench_function("uncached", |b| b.iter(|| {
+        black_box(MerkleTree::new(changed.clone()));
+    }));
+    group.bench_function("cached", |b| b.iter_batched(
+        || {
+            let mut cache = MerkleTreeCache::new();
+            MerkleTree::new_cached(hashes.clone(), &mut cache);
+            cache
+        },
+        |mut cache| black_box(MerkleTree::new_cached(changed.clone(), &mut cache)),
+        BatchSize::LargeInput,
+    ));
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_create_merkle_tree,
     bench_generate_proof,
+    bench_verify_long_proofs,
+    bench_proofs_large_tree,
+    bench_rebuild_cached,
 );
 criterion_main!(benches);
\ No newline at end of file
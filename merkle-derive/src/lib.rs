@@ -0,0 +1,43 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index};
+
+#[proc_macro_derive(MerkleLeaf)]
+pub fn derive_merkle_leaf(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields: Vec<TokenStream2> = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().map(|f| {
+                let ident = &f.ident;
+                quote! { &self.#ident }
+            }).collect(),
+            Fields::Unnamed(fields) => (0..fields.unnamed.len()).map(|i| {
+                let index = Index::from(i);
+                quote! { &self.#index }
+            }).collect(),
+            Fields::Unit => vec![],
+        },
+        _ => {
+            return syn::Error::new_spanned(&input.ident, "MerkleLeaf can only be derived for structs")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    // fields are encoded in declaration order
+    let expanded = quote! {
+        impl #impl_generics ::merkle::merkle::MerkleLeaf for #name #ty_generics #where_clause {
+            fn to_leaf_bytes(&self) -> ::merkle::Vec<u8> {
+                let mut bytes = ::merkle::Vec::new();
+                #( bytes.extend(::merkle::merkle::MerkleLeaf::to_leaf_bytes(#fields)); )*
+                bytes
+            }
+        }
+    };
+
+    expanded.into()
+}
@@ -0,0 +1,23 @@
+use hmac_sha256::Hash;
+use merkle::merkle::{MerkleLeaf, MerkleTree};
+
+#[derive(MerkleLeaf)]
+struct Account {
+    name: String,
+    balance: u64,
+}
+
+#[test]
+fn test_derive_merkle_leaf() {
+    let accounts = vec![
+        Account { name: "alice".to_string(), balance: 100 },
+        Account { name: "bob".to_string(), balance: 42 },
+    ];
+
+    assert_eq!(accounts[0].to_leaf_bytes(), [b"alice".to_vec(), 100u64.to_le_bytes().to_vec()].concat());
+
+    let mtree = MerkleTree::from_leaves(&accounts);
+    let hashes: Vec<[u8; 32]> = accounts.iter().map(|a| Hash::hash(&a.to_leaf_bytes())).collect();
+
+    assert_eq!(mtree.root_hash(), MerkleTree::new(hashes).root_hash());
+}
@@ -0,0 +1,41 @@
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use merkle::merkle::{MerkleError, MerkleLeaf, MerkleProof, MerkleTree, Sha256Hasher, MerkleHasher};
+use merkle::sparse::SparseMerkleTree;
+
+#[derive(MerkleLeaf)]
+pub struct Transfer {
+    pub amount: u64,
+    pub fee: u32,
+}
+
+pub fn root_and_proof(data: &[&[u8]], index: usize) -> Result<([u8; 32], MerkleProof), MerkleError> {
+    let leaves: Vec<[u8; 32]> = data.iter().map(|d| Sha256Hasher::hash(d)).collect();
+    let tree = MerkleTree::try_new(leaves)?;
+    let proof = tree.generate_proofs(Sha256Hasher::hash(data[index]))?;
+
+    Ok((*tree.root_hash(), proof))
+}
+
+pub fn check(data: &[&[u8]], index: usize) -> bool {
+    match root_and_proof(data, index) {
+        Ok((root, proof)) => proof.verify(&root),
+        Err(_) => false,
+    }
+}
+
+pub fn transfers_root(transfers: &[Transfer]) -> [u8; 32] {
+    *MerkleTree::from_leaves(transfers).root_hash()
+}
+
+pub fn sparse_root(entries: &[(&[u8], &[u8])]) -> [u8; 32] {
+    let mut smt = SparseMerkleTree::new();
+    for (key, value) in entries {
+        smt.insert(key, value);
+    }
+
+    smt.root()
+}
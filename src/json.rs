@@ -0,0 +1,115 @@
+// just enough JSON to read back the proof formats we export: an array of flat objects
+// holding string or boolean values
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter::Peekable;
+use core::str::Chars;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum JsonValue {
+    Str(String),
+    Bool(bool),
+}
+
+pub(crate) type JsonObject = Vec<(String, JsonValue)>;
+
+pub(crate) fn get<'a>(object: &'a JsonObject, key: &str) -> Option<&'a JsonValue> {
+    object.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+pub(crate) fn parse_object_array(input: &str) -> Option<Vec<JsonObject>> {
+    let mut chars = input.chars().peekable();
+    let mut objects = vec![];
+
+    expect(&mut chars, '[')?;
+    if peek(&mut chars)? == ']' {
+        chars.next();
+    } else {
+        loop {
+            objects.push(parse_object(&mut chars)?);
+            match next(&mut chars)? {
+                ',' => continue,
+                ']' => break,
+                _ => return None,
+            }
+        }
+    }
+
+    // nothing but whitespace may follow the array
+    if peek(&mut chars).is_some() {
+        return None;
+    }
+
+    Some(objects)
+}
+
+fn parse_object(chars: &mut Peekable<Chars>) -> Option<JsonObject> {
+    let mut object = vec![];
+
+    expect(chars, '{')?;
+    if peek(chars)? == '}' {
+        chars.next();
+        return Some(object);
+    }
+
+    loop {
+        let key = parse_string(chars)?;
+        expect(chars, ':')?;
+        let value = match peek(chars)? {
+            '"' => JsonValue::Str(parse_string(chars)?),
+            't' => { parse_literal(chars, "true")?; JsonValue::Bool(true) },
+            'f' => { parse_literal(chars, "false")?; JsonValue::Bool(false) },
+            _ => return None,
+        };
+        object.push((key, value));
+
+        match next(chars)? {
+            ',' => continue,
+            '}' => break,
+            _ => return None,
+        }
+    }
+
+    Some(object)
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    expect(chars, '"')?;
+
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(s),
+            '\\' => match chars.next()? {
+                c @ ('"' | '\\' | '/') => s.push(c),
+                _ => return None,
+            },
+            c => s.push(c),
+        }
+    }
+}
+
+fn parse_literal(chars: &mut Peekable<Chars>, literal: &str) -> Option<()> {
+    for c in literal.chars() {
+        if chars.next()? != c {
+            return None;
+        }
+    }
+    Some(())
+}
+
+// skips whitespace before looking at the next char
+fn peek(chars: &mut Peekable<Chars>) -> Option<char> {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    chars.peek().copied()
+}
+
+fn next(chars: &mut Peekable<Chars>) -> Option<char> {
+    peek(chars)?;
+    chars.next()
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Option<()> {
+    (next(chars)? == expected).then_some(())
+}
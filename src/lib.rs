@@ -1 +1,14 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+// the path `#[derive(MerkleLeaf)]` names Vec by, so it expands the same with or without std
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub use alloc::vec::Vec;
+
+mod json;
 pub mod merkle;
+pub mod sparse;
+#[cfg(feature = "wasm")]
+pub mod wasm;
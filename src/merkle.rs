@@ -1,380 +1,4282 @@
-use hmac_sha256::Hash;
-use std::error::Error;
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::rc::Weak;
+use crate::json::{self, JsonValue};
+use sha2::{Digest, Sha256};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::error::Error;
+use core::fmt;
+use core::marker::PhantomData;
 
-pub struct MerkleTree {
-    root: Rc<Node>,
-    leaves: Vec<Rc<Node>>,
+#[cfg(feature = "derive")]
+pub use merkle_derive::MerkleLeaf;
+
+pub type Proof<const N: usize = 32> = Vec<([u8; N], u8)>;
+
+// a serialized proof step is the 32 sibling bytes followed by the direction byte
+pub const PROOF_STEP_SIZE: usize = 33;
+
+// N is the width of the hash in bytes, e.g. 20 for RIPEMD160 or 64 for SHA-512. The nodes,
+// proofs and roots of a tree over this hasher are all [u8; N].
+pub trait MerkleHasher<const N: usize = 32> {
+    // identifies the hash function, recorded in serialized trees. Proof encodings don't carry it,
+    // both sides have to agree on the hasher up front
+    const ALGORITHM_ID: &'static str;
+
+    fn hash(data: &[u8]) -> [u8; N];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    const ALGORITHM_ID: &'static str = "sha256";
+
+    fn hash(data: &[u8]) -> [u8; 32] {
+        // sha2 picks up the CPU SHA extensions when they are available
+        Sha256::digest(data).into()
+    }
+}
+
+// bytes a value contributes to a leaf, `#[derive(MerkleLeaf)]` concatenates the fields in declaration order
+pub trait MerkleLeaf {
+    fn to_leaf_bytes(&self) -> Vec<u8>;
+}
+
+macro_rules! impl_merkle_leaf_int {
+    ($($t:ty),*) => {
+        $(impl MerkleLeaf for $t {
+            fn to_leaf_bytes(&self) -> Vec<u8> {
+                self.to_le_bytes().to_vec()
+            }
+        })*
+    };
+}
+
+impl_merkle_leaf_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl MerkleLeaf for bool {
+    fn to_leaf_bytes(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+}
+
+impl MerkleLeaf for str {
+    fn to_leaf_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl MerkleLeaf for String {
+    fn to_leaf_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl MerkleLeaf for [u8] {
+    fn to_leaf_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl MerkleLeaf for Vec<u8> {
+    fn to_leaf_bytes(&self) -> Vec<u8> {
+        self.clone()
+    }
+}
+
+impl<const N: usize> MerkleLeaf for [u8; N] {
+    fn to_leaf_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MerkleError {
+    EmptyTree,
+    MalformedProof,
+    ProofCountMismatch,
+    InconsistentProofs,
+    IndexOutOfBounds,
+    InvalidHex,
+    LeafNotFound,
+    InvalidTruncation,
+    RootMismatch,
+    ProofTooLong,
+    DuplicateLeaf(usize),
+    LeafMismatch,
+    BadLeafLength { index: usize, len: usize },
+    UnsupportedOddNodeStrategy,
+    ConfigMismatch,
+}
+
+impl fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MerkleError::EmptyTree => write!(f, "merkle tree has no leaves"),
+            MerkleError::MalformedProof => write!(f, "malformed proof"),
+            MerkleError::ProofCountMismatch => write!(f, "number of proofs doesn't match number of leaves"),
+            MerkleError::InconsistentProofs => write!(f, "proofs disagree on an internal node hash"),
+            MerkleError::IndexOutOfBounds => write!(f, "leaf index out of bounds"),
+            MerkleError::InvalidHex => write!(f, "invalid hex encoded hash"),
+            MerkleError::LeafNotFound => write!(f, "leaf not found in the tree"),
+            MerkleError::InvalidTruncation => write!(f, "hash truncation must be between 1 byte and the hash width"),
+            MerkleError::RootMismatch => write!(f, "computed root doesn't match the expected root"),
+            MerkleError::ProofTooLong => write!(f, "proof has more steps than the tree is deep"),
+            MerkleError::DuplicateLeaf(index) => write!(f, "leaf {} is a duplicate of an earlier leaf", index),
+            MerkleError::LeafMismatch => write!(f, "data doesn't hash to the leaf the proof is for"),
+            MerkleError::BadLeafLength { index, len } => write!(f, "leaf {} is {} bytes long, not a hash", index, len),
+            MerkleError::UnsupportedOddNodeStrategy => write!(f, "not supported for trees promoting their odd nodes"),
+            MerkleError::ConfigMismatch => write!(f, "trees don't hash their nodes the same way"),
+        }
+    }
+}
+
+// std already converts any Error into a Box<dyn Error>, so `?` keeps working for boxed callers
+impl Error for MerkleError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyOutcome<const N: usize = 32> {
+    Valid,
+    // the proof is well formed but leads to another root, first_mismatch is the first byte that differs
+    Invalid { computed_root: [u8; N], first_mismatch: usize },
+    Malformed { reason: &'static str },
+}
+
+// what `verify_with_info` went through, for logging a verification that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyResult<const N: usize = 32> {
+    pub computed_root: [u8; N],
+    pub steps_applied: usize,
+}
+
+pub type MerkleTree = GenericMerkleTree<Sha256Hasher>;
+pub type Sha256MerkleTree = MerkleTree;
+
+// leaf hash to the position of its first occurrence
+#[cfg(feature = "std")]
+type LeafIndex<const N: usize> = std::collections::HashMap<LeafKey<N>, usize>;
+#[cfg(not(feature = "std"))]
+type LeafIndex<const N: usize> = BTreeMap<LeafKey<N>, usize>;
+
+// parent hash keyed by its (left, right) children
+#[cfg(feature = "std")]
+type ParentMap<const N: usize> = std::collections::HashMap<([u8; N], [u8; N]), [u8; N]>;
+#[cfg(not(feature = "std"))]
+type ParentMap<const N: usize> = BTreeMap<([u8; N], [u8; N]), [u8; N]>;
+
+// keys the leaf index by the leaf node itself so the hash isn't copied out of it,
+// it compares and hashes exactly like the [u8; N] it borrows as
+#[derive(Debug, Clone)]
+struct LeafKey<const N: usize>(Arc<Node<N>>);
+
+impl<const N: usize> Borrow<[u8; N]> for LeafKey<N> {
+    fn borrow(&self) -> &[u8; N] {
+        self.0.known_hash()
+    }
+}
+
+impl<const N: usize> PartialEq for LeafKey<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.known_hash() == other.0.known_hash()
+    }
+}
+
+impl<const N: usize> Eq for LeafKey<N> {}
+
+impl<const N: usize> PartialOrd for LeafKey<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const N: usize> Ord for LeafKey<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.known_hash().cmp(other.0.known_hash())
+    }
+}
+
+impl<const N: usize> core::hash::Hash for LeafKey<N> {
+    fn hash<S: core::hash::Hasher>(&self, state: &mut S) {
+        self.0.known_hash().hash(state)
+    }
+}
+
+pub struct GenericMerkleTree<H: MerkleHasher<N>, const N: usize = 32> {
+    root: Arc<Node<N>>,
+    leaves: Vec<Arc<Node<N>>>,
+    leaf_index: LeafIndex<N>,
+    // original_order[i] is the input position of the leaf now at position i
+    original_order: Vec<usize>,
+    config: TreeConfig,
+    hasher: PhantomData<fn() -> H>,
+}
+
+// how nodes get hashed, construction and verification have to agree on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TreeConfig {
+    // number of bytes kept from every internal hash, None keeps all of them
+    truncate: Option<usize>,
+    // prefix leaves with 0x00 and internal nodes with 0x01
+    domain_separation: bool,
+    // order each pair by bytes before hashing, like OpenZeppelin's MerkleProof
+    sorted_pairs: bool,
+    odd_node: OddNodeStrategy,
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        Self { truncate: None, domain_separation: false, sorted_pairs: false, odd_node: OddNodeStrategy::Duplicate }
+    }
+}
+
+// what happens to the last node of a level with an odd number of nodes. Promoted nodes have no
+// sibling so their proofs skip that level: proofs get shorter than the depth and the leaf index
+// can't be read back from the direction bytes. Multiproofs, range proofs and consistency proofs
+// need Duplicate and return UnsupportedOddNodeStrategy otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OddNodeStrategy {
+    // hashed with a copy of itself, like Bitcoin does
+    #[default]
+    Duplicate,
+    // carried up to the next level unchanged
+    Promote,
 }
 
-impl MerkleTree {
-    pub fn new(leaves: Vec<[u8; 32]>) -> Self {
+// what an empty tree reports as its root, `compute_root` agrees
+const EMPTY_ROOT: [u8; 32] = [0u8; 32];
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+impl<H: MerkleHasher<N>, const N: usize> GenericMerkleTree<H, N> {
+    pub fn new(leaves: Vec<[u8; N]>) -> Self {
+        Self::build(leaves, TreeConfig::default())
+    }
+
+    // Panic free for any input: there are no parent links to set anymore, Empty nodes never get
+    // hashed and the only input `new` can't make a proper tree of is an empty one.
+    pub fn try_new(leaves: Vec<[u8; N]>) -> Result<Self, MerkleError> {
+        if leaves.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+
+        Ok(Self::new(leaves))
+    }
+
+    // leaves go straight from the iterator into nodes, `collect()` works too through FromIterator
+    pub fn try_from_iter<I: IntoIterator<Item = [u8; N]>>(iter: I) -> Result<Self, MerkleError> {
+        let tree = Self::build(iter, TreeConfig::default());
+        if tree.leaves.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+
+        Ok(tree)
+    }
+
+    #[deprecated(note = "use `try_new`")]
+    pub fn try_build(leaves: Vec<[u8; N]>) -> Result<Self, MerkleError> {
+        Self::try_new(leaves)
+    }
+
+    // leaf hashes coming in as bytes, each has to be exactly as long as the hasher's output
+    pub fn try_from_vecs(leaves: Vec<Vec<u8>>) -> Result<Self, MerkleError> {
+        let hashes = leaves.iter().enumerate().map(|(index, leaf)| {
+            <[u8; N]>::try_from(leaf.as_slice()).map_err(|_| MerkleError::BadLeafLength { index, len: leaf.len() })
+        }).collect::<Result<Vec<[u8; N]>, MerkleError>>()?;
+
+        Self::try_new(hashes)
+    }
+
+    // like `new` but every leaf hash has to be unique, the error holds the index of the first repeat
+    pub fn new_checked(leaves: Vec<[u8; N]>) -> Result<Self, MerkleError> {
+        let mut seen = BTreeSet::new();
+        if let Some(index) = leaves.iter().position(|leaf| !seen.insert(*leaf)) {
+            return Err(MerkleError::DuplicateLeaf(index));
+        }
+
+        Ok(Self::new(leaves))
+    }
+
+    // Internal hashes only keep their first `t` bytes, the rest is zeroed. This makes proofs
+    // smaller but finding a collision on a t-byte hash takes about 2^(4t) work instead of 2^128,
+    // t = 16 leaves a 64-bit security level which is too low for anything adversarial.
+    // Leaves are kept as given, verify with `verify_truncated` and the same `t`.
+    pub fn new_truncated(leaves: Vec<[u8; N]>, t: usize) -> Result<Self, MerkleError> {
+        let truncate = checked_truncation::<N>(t)?;
+        if leaves.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+
+        Ok(Self::build(leaves, TreeConfig { truncate, ..TreeConfig::default() }))
+    }
+
+    // Leaves are expected to be hashed as 0x00 || data (see `domain_separated_leaf`) and internal
+    // nodes get hashed as 0x01 || left || right, so an internal node can never pass for a leaf.
+    // Roots differ from `new`, verify with `verify_with_domain_separation`.
+    pub fn new_with_domain_separation(leaves: Vec<[u8; N]>) -> Self {
+        Self::build(leaves, TreeConfig { domain_separation: true, ..TreeConfig::default() })
+    }
+
+    // Each pair is hashed smallest first, so proofs don't depend on directions and verify with
+    // OpenZeppelin's MerkleProof.verify given the same hash function. Not to be confused with
+    // `new_with_sorted_leaves` which sorts the leaves, verify with `verify_sorted`.
+    pub fn new_sorted(leaves: Vec<[u8; N]>) -> Self {
+        Self::build(leaves, TreeConfig { sorted_pairs: true, ..TreeConfig::default() })
+    }
+
+    pub fn domain_separated_leaf(data: &[u8]) -> [u8; N] {
+        H::hash(&[&[LEAF_PREFIX], data].concat())
+    }
+
+    fn build(leaves: impl IntoIterator<Item = [u8; N]>, config: TreeConfig) -> Self {
+        let nodes: Vec<Arc<Node<N>>> = leaves.into_iter().map(|hash| {
+            Arc::new(Node::Leaf { hash })
+        }).collect();
+
+        let root = Self::build_tree(&nodes, &config);
+
+        Self::assemble(nodes, root, config)
+    }
+
+    // same tree as `new`, parent hashes already in `cache` aren't computed again. Rebuilding
+    // with a few leaves changed only hashes the paths from those leaves up to the root.
+    pub fn new_cached(leaves: Vec<[u8; N]>, cache: &mut MerkleTreeCache<H, N>) -> Self {
+        let config = TreeConfig::default();
+        let nodes: Vec<Arc<Node<N>>> = leaves.into_iter().map(|hash| Arc::new(Node::Leaf { hash })).collect();
+
+        let root = Self::build_tree_with(&nodes, |left, right| {
+            let left = left.known_hash();
+            // the odd node out is paired with itself
+            let right = right.hash().unwrap_or(left);
+            *cache.parents.entry((*left, *right)).or_insert_with(|| Self::hash_children(left, right, &config))
+        });
+
+        Self::assemble(nodes, root, config)
+    }
+
+    fn assemble(nodes: Vec<Arc<Node<N>>>, root: Arc<Node<N>>, config: TreeConfig) -> Self {
+        let mut leaf_index = LeafIndex::new();
+        for (i, leaf) in nodes.iter().enumerate() {
+            leaf_index.entry(LeafKey(Arc::clone(leaf))).or_insert(i);
+        }
+
+        Self { root, original_order: (0..nodes.len()).collect(), leaves: nodes, leaf_index, config, hasher: PhantomData }
+    }
+
+    // builds the tree and checks it against a root we were given along with the leaves
+    pub fn verify_build(leaves: Vec<[u8; N]>, claimed_root: &[u8; N]) -> Result<Self, MerkleError> {
+        if leaves.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+
+        let tree = Self::new(leaves);
+        if tree.root_hash() != claimed_root {
+            return Err(MerkleError::RootMismatch);
+        }
+
+        Ok(tree)
+    }
+
+    // leaves are sorted by hash before building, the input order is kept around
+    pub fn new_with_sorted_leaves(leaves: Vec<[u8; N]>) -> Self {
+        let mut order: Vec<usize> = (0..leaves.len()).collect();
+        order.sort_by_key(|&i| leaves[i]);
+
+        let mut tree = Self::new(order.iter().map(|&i| leaves[i]).collect());
+        tree.original_order = order;
+
+        tree
+    }
+
+    pub fn original_index(&self, sorted_index: usize) -> Result<usize, MerkleError> {
+        self.original_order.get(sorted_index).copied().ok_or(MerkleError::IndexOutOfBounds)
+    }
+
+    // leaves ordered by hash along with their position in the tree, equal hashes keep their order
+    pub fn sorted_view(&self) -> Vec<(usize, [u8; N])> {
+        let mut view: Vec<(usize, [u8; N])> = self.leaves.iter().map(|l| *l.known_hash()).enumerate().collect();
+        view.sort_by_key(|(_, hash)| *hash);
+
+        view
+    }
+
+    pub fn from_leaves<T: MerkleLeaf>(items: &[T]) -> Self {
+        let hashes: Vec<[u8; N]> = items.iter().map(|item| H::hash(&item.to_leaf_bytes())).collect();
+
+        Self::new(hashes)
+    }
+
+    // the caller picks how an item turns into its leaf hash, e.g. over some canonical encoding
+    pub fn from_items_with<T, F: Fn(&T) -> [u8; N]>(items: Vec<T>, hash_fn: F) -> Self {
+        Self::build(items.iter().map(hash_fn), TreeConfig::default())
+    }
+
+    // a top level tree whose leaves are the roots of `subtrees`, see `combined_proof`
+    pub fn of_subtrees(subtrees: &[Self]) -> Self {
+        Self::build(subtrees.iter().map(|subtree| *subtree.root_hash()), TreeConfig::default())
+    }
+
+    // hashes every item into a leaf, errors on an empty input like `try_new`
+    pub fn from_data<T: AsRef<[u8]>>(items: Vec<T>) -> Result<Self, MerkleError> {
+        let hashes: Vec<[u8; N]> = items.iter().map(|item| H::hash(item.as_ref())).collect();
+
+        Self::try_new(hashes)
+    }
+
+    // internal node hash, following the tree config
+    fn hash_children(left: &[u8; N], right: &[u8; N], config: &TreeConfig) -> [u8; N] {
+        let (left, right) = if config.sorted_pairs && left > right { (right, left) } else { (left, right) };
+        let hash = hash_concat::<H, N>(config.domain_separation.then_some(NODE_PREFIX), left, right);
+
+        match config.truncate {
+            Some(t) => truncate_hash(hash, t),
+            None => hash,
+        }
+    }
+
+    // hash of the node over `left` and `right`, an Empty right means `left` is the odd node out
+    fn hash_node(left: &Node<N>, right: &Node<N>, config: &TreeConfig) -> [u8; N] {
+        match (right, config.odd_node) {
+            (Node::Empty, OddNodeStrategy::Duplicate) => Self::hash_children(left.known_hash(), left.known_hash(), config),
+            (Node::Empty, OddNodeStrategy::Promote) => *left.known_hash(),
+            _ => Self::hash_children(left.known_hash(), right.known_hash(), config),
+        }
+    }
+
+    fn build_tree(items: &[Arc<Node<N>>], config: &TreeConfig) -> Arc<Node<N>> {
+        Self::build_tree_with(items, |left, right| Self::hash_node(left, right, config))
+    }
+
+    // `hash_node` gets the children of every internal node, right being Empty for an odd node out
+    fn build_tree_with(items: &[Arc<Node<N>>], mut hash_node: impl FnMut(&Node<N>, &Node<N>) -> [u8; N]) -> Arc<Node<N>> {
+        // without any leaf the root is Empty, same as `compute_root` gives
+        if items.is_empty() {
+            return Arc::new(Node::Empty);
+        }
+
+        // hash one level into the next until only the root is left
+        let mut level: Vec<Arc<Node<N>>> = items.to_vec();
+        while level.len() > 1 {
+            let mut nodes: Vec<Arc<Node<N>>> = Vec::with_capacity(level.len().div_ceil(2));
+            for i in (0..level.len()).step_by(2) {
+                let left = Arc::clone(&level[i]);
+                // if we have an odd number of nodes the last one is left without a sibling
+                let right = level.get(i + 1).map_or_else(|| Arc::new(Node::Empty), Arc::clone);
+                let hash = hash_node(&left, &right);
+
+                nodes.push(Arc::new(Node::Node { hash, left, right }));
+            }
+
+            level = nodes;
+        }
+
+        level.remove(0)
+    }
+
+    pub fn truncation(&self) -> usize {
+        self.config.truncate.unwrap_or(N)
+    }
+
+    pub fn domain_separation(&self) -> bool {
+        self.config.domain_separation
+    }
+
+    pub fn odd_node(&self) -> OddNodeStrategy {
+        self.config.odd_node
+    }
+
+    // verifies proofs of this tree with the same hashing rules it was built with
+    pub fn verifier(&self) -> Verifier<H, N> {
+        Verifier { config: self.config, hasher: PhantomData }
+    }
+
+    pub fn algorithm() -> &'static str {
+        H::ALGORITHM_ID
+    }
+
+    pub fn root_hash(&self) -> &[u8; N] {
+        self.root.hash().unwrap_or(&[0u8; N])
+    }
+
+    // an owned copy of the root, to keep once the tree is gone
+    pub fn root_bytes(&self) -> [u8; N] {
+        *self.root_hash()
+    }
+
+    // lowercase, without a 0x prefix
+    pub fn root_hex(&self) -> String {
+        hex::encode(self.root_hash())
+    }
+
+    pub fn root(&self) -> &Node<N> {
+        self.root.as_ref()
+    }
+
+    // commits to the number of leaves as well, hash(root || leaf_count as u64 little endian)
+    pub fn sized_root(&self) -> [u8; N] {
+        Self::size_commitment(self.root_hash(), self.leaves.len())
+    }
+
+    fn size_commitment(root: &[u8; N], leaf_count: usize) -> [u8; N] {
+        H::hash(&[&root[..], &(leaf_count as u64).to_le_bytes()].concat())
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    // levels from a leaf up to the root, 0 for a single leaf
+    pub fn depth(&self) -> usize {
+        let mut height = 0;
+        let mut n = self.root();
+        while let Some(left) = n.get_left() {
+            height += 1;
+            n = left;
+        }
+
+        height
+    }
+
+    // leaf nodes in tree order
+    pub fn leaves(&self) -> impl Iterator<Item = &Node<N>> + '_ {
+        self.leaves.iter().map(|l| l.as_ref())
+    }
+
+    pub fn leaf_hashes_iter(&self) -> impl Iterator<Item = &[u8; N]> + '_ {
+        self.leaves().map(Node::known_hash)
+    }
+
+    // in the order they were given, which differs from the tree order for `new_with_sorted_leaves`
+    pub fn leaf_hashes(&self) -> Vec<[u8; N]> {
+        let mut hashes = vec![[0u8; N]; self.leaves.len()];
+        for (leaf, &original) in self.leaves.iter().zip(&self.original_order) {
+            hashes[original] = *leaf.known_hash();
+        }
+
+        hashes
+    }
+
+    pub fn contains(&self, hash: &[u8; N]) -> bool {
+        self.leaf_index(hash).is_some()
+    }
+
+    // position of the leaf, the first one when the same hash appears several times
+    pub fn leaf_index(&self, hash: &[u8; N]) -> Option<usize> {
+        self.leaf_index.get(hash).copied()
+    }
+
+    pub fn generate_proofs(&self, hash: [u8; N]) -> Result<MerkleProof<N>, MerkleError> {
+        // lookup for our leaf
+        match self.leaf_index(&hash) {
+            Some(index) => Ok(MerkleProof::from_tuples(hash, &self.generate_proof_by_index(index)?)),
+            None => Err(MerkleError::LeafNotFound),
+        }
+    }
+
+    // hashes on the way from the leaf up to the root, both included
+    pub fn path_hashes(&self, leaf_hash: &[u8; N]) -> Result<Vec<[u8; N]>, MerkleError> {
+        let index = self.leaf_index(leaf_hash).ok_or(MerkleError::LeafNotFound)?;
+
+        let mut path = Vec::with_capacity(self.depth() + 1);
+        let mut n = self.root();
+        path.push(*n.known_hash());
+        for h in (0..self.depth()).rev() {
+            let Some(left) = n.get_left() else { break };
+            n = if (index >> h) & 1 == 0 { left } else { n.get_right().unwrap() };
+            path.push(*n.known_hash());
+        }
+
+        path.reverse();
+        Ok(path)
+    }
+
+    // one step per level, but for the levels a promoted node is carried up without a sibling
+    pub fn proof_len(&self, hash: &[u8; N]) -> Result<usize, MerkleError> {
+        let index = self.leaf_index(hash).ok_or(MerkleError::LeafNotFound)?;
+        if self.config.odd_node == OddNodeStrategy::Duplicate {
+            return Ok(self.depth());
+        }
+
+        let mut len = 0;
+        let mut n = self.root();
+        for h in (0..self.depth()).rev() {
+            let Some(left) = n.get_left() else { break };
+            let right = n.get_right();
+            if right.is_some() {
+                len += 1;
+            }
+            n = if (index >> h) & 1 == 0 { left } else { right.unwrap() };
+        }
+
+        Ok(len)
+    }
+
+    #[deprecated(note = "use `generate_proofs`, which returns a `MerkleProof<N>`")]
+    pub fn generate_proof_tuples(&self, hash: [u8; N]) -> Result<Proof<N>, MerkleError> {
+        self.generate_proofs(hash).map(MerkleProof::into_tuples)
+    }
+
+    // only reads the tree so it can be shared between threads behind an `Arc`
+    pub fn generate_proof_by_index(&self, index: usize) -> Result<Proof<N>, MerkleError> {
+        if index >= self.leaves.len() {
+            return Err(MerkleError::IndexOutOfBounds);
+        }
+
+        Ok(self.root().gen_proof(self.depth(), index, self.config.odd_node))
+    }
+
+    // like `generate_proofs` but the sibling hashes are borrowed from the tree instead of copied
+    pub fn generate_proof_refs(&self, hash: [u8; N]) -> Result<Vec<(&[u8; N], u8)>, MerkleError> {
+        let index = self.leaf_index(&hash).ok_or(MerkleError::LeafNotFound)?;
+
+        Ok(self.root().gen_proof_refs(self.depth(), index, self.config.odd_node))
+    }
+
+    // only the ancestors of the new leaf change, every other subtree of the current tree is reused
+    pub fn push_leaf(&mut self, hash: [u8; N]) {
+        let index = self.leaves.len();
+        let leaf = Arc::new(Node::Leaf { hash });
+
+        let mut depth = 0;
+        while (1 << depth) < index + 1 {
+            depth += 1;
+        }
+
+        let mut node = Arc::clone(&leaf);
+        for h in 0..depth {
+            let pos = index >> h;
+            let (left, right) = if pos & 1 == 0 {
+                // the last node of its level, it gets paired with itself
+                (node, Arc::new(Node::Empty))
+            } else {
+                (Arc::clone(self.node_at(h, pos - 1)), node)
+            };
+            let hash = Self::hash_node(&left, &right, &self.config);
+            node = Arc::new(Node::Node { hash, left, right });
+        }
+
+        self.root = node;
+        self.leaf_index.entry(LeafKey(Arc::clone(&leaf))).or_insert(index);
+        self.leaves.push(leaf);
+        self.original_order.push(index);
+    }
+
+    // ends up with the same root as `new([old leaves, new_hashes].concat())`, one `push_leaf` at a time
+    pub fn extend_leaves(&mut self, new_hashes: Vec<[u8; N]>) {
+        for hash in new_hashes {
+            self.push_leaf(hash);
+        }
+    }
+
+    // node at `level` (leaves being 0) and position `pos` in that level, which has to exist
+    fn node_at(&self, level: usize, pos: usize) -> &Arc<Node<N>> {
+        let mut n = &self.root;
+        for l in (level..self.depth()).rev() {
+            let Node::Node { left, right, .. } = n.as_ref() else { break };
+            n = if (pos >> (l - level)) & 1 == 0 { left } else { right };
+        }
+
+        n
+    }
+
+    // nodes are shared so the path from the root to the leaf is copied, everything else is reused
+    pub fn update_leaf(&mut self, old_hash: [u8; N], new_hash: [u8; N]) -> Result<(), MerkleError> {
+        let index = self.leaf_index(&old_hash).ok_or(MerkleError::LeafNotFound)?;
+
+        // ancestors of the leaf, root first
+        let mut path: Vec<&Arc<Node<N>>> = Vec::with_capacity(self.depth());
+        let mut n = &self.root;
+        for h in (0..self.depth()).rev() {
+            let Node::Node { left, right, .. } = n.as_ref() else { break };
+            path.push(n);
+            n = if (index >> h) & 1 == 0 { left } else { right };
+        }
+
+        let leaf = Arc::new(Node::Leaf { hash: new_hash });
+        let mut node = Arc::clone(&leaf);
+        for (h, parent) in path.into_iter().rev().enumerate() {
+            let Node::Node { left, right, .. } = parent.as_ref() else { unreachable!() };
+            let (left, right) = if (index >> h) & 1 == 0 { (node, Arc::clone(right)) } else { (Arc::clone(left), node) };
+            let hash = Self::hash_node(&left, &right, &self.config);
+            node = Arc::new(Node::Node { hash, left, right });
+        }
+
+        self.root = node;
+        self.leaves[index] = leaf;
+
+        // the old hash may still be further down the leaves, the new one may already be there
+        self.leaf_index.remove(&old_hash);
+        if let Some(next) = self.leaves[index..].iter().position(|l| l.known_hash() == &old_hash) {
+            self.leaf_index.insert(LeafKey(Arc::clone(&self.leaves[index + next])), index + next);
+        }
+        let first = self.leaf_index.entry(LeafKey(Arc::clone(&self.leaves[index]))).or_insert(index);
+        *first = (*first).min(index);
+
+        Ok(())
+    }
+
+    // one proof for several leaves, verify it with the leaf hashes ordered like its `indices`
+    pub fn generate_multiproof(&self, hashes: &[[u8; N]]) -> Result<MultiProof<N>, MerkleError> {
+        if self.config.odd_node == OddNodeStrategy::Promote {
+            return Err(MerkleError::UnsupportedOddNodeStrategy);
+        }
+
+        let proofs = hashes.iter().map(|hash| {
+            let index = self.leaf_index(hash).ok_or(MerkleError::LeafNotFound)?;
+            Ok((index, self.generate_proof_by_index(index)?))
+        }).collect::<Result<Vec<(usize, Proof<N>)>, MerkleError>>()?;
+
+        Ok(MultiProof { config: self.config, ..combine_proofs(&proofs, self.leaves.len()) })
+    }
+
+    // proves the leaves in `start..end`, only the subtree roots bordering the range are carried
+    pub fn range_proof(&self, start: usize, end: usize) -> Result<RangeProof<N>, MerkleError> {
+        if self.config.odd_node == OddNodeStrategy::Promote {
+            return Err(MerkleError::UnsupportedOddNodeStrategy);
+        }
+        if start >= end || end > self.leaves.len() {
+            return Err(MerkleError::IndexOutOfBounds);
+        }
+
+        let proofs = (start..end)
+            .map(|index| Ok((index, self.generate_proof_by_index(index)?)))
+            .collect::<Result<Vec<(usize, Proof<N>)>, MerkleError>>()?;
+        let MultiProof { leaf_count, height, nodes, .. } = combine_proofs(&proofs, self.leaves.len());
+
+        Ok(RangeProof { start, end, leaf_count, height, nodes, config: self.config })
+    }
+
+    // `self` is built with `of_subtrees` and `subtree` is the one at `subtree_index`. The proof of
+    // `leaf_hash` inside it goes on with the proof of its root, so `verify` reaches our root.
+    pub fn combined_proof(&self, subtree: &Self, subtree_index: usize, leaf_hash: [u8; N]) -> Result<Proof<N>, MerkleError> {
+        let top = self.generate_proof_by_index(subtree_index)?;
+        if self.leaves[subtree_index].known_hash() != subtree.root_hash() {
+            return Err(MerkleError::RootMismatch);
+        }
+
+        let index = subtree.leaf_index(&leaf_hash).ok_or(MerkleError::LeafNotFound)?;
+        let mut proofs = subtree.generate_proof_by_index(index)?;
+        proofs.extend(top);
+
+        Ok(proofs)
+    }
+
+    // proves the tree of our first `old_size` leaves is a prefix of this one, see `verify_consistency`
+    pub fn consistency_proof(&self, old_size: usize) -> Result<Vec<[u8; N]>, MerkleError> {
+        if self.config.odd_node == OddNodeStrategy::Promote {
+            return Err(MerkleError::UnsupportedOddNodeStrategy);
+        }
+        if old_size == 0 || old_size > self.leaves.len() {
+            return Err(MerkleError::IndexOutOfBounds);
+        }
+        if old_size == self.leaves.len() {
+            return Ok(vec![]);
+        }
+
+        // start from the biggest complete subtree holding the last old leaf, it is the same node
+        // in both trees. The verifier already has it when it is the whole old tree.
+        let level = old_size.trailing_zeros() as usize;
+        let mut pos = (old_size - 1) >> level;
+        let mut proof = vec![];
+        if !old_size.is_power_of_two() {
+            proof.push(*self.node_at(level, pos).known_hash());
+        }
+
+        let mut width = self.leaves.len().div_ceil(1 << level);
+        for l in level.. {
+            if width <= 1 {
+                break;
+            }
+            if pos % 2 == 1 {
+                proof.push(*self.node_at(l, pos - 1).known_hash());
+            } else if pos + 1 < width {
+                proof.push(*self.node_at(l, pos + 1).known_hash());
+            }
+
+            pos /= 2;
+            width = width.div_ceil(2);
+        }
+
+        Ok(proof)
+    }
+
+    pub fn prune_to(&self, indices: &[usize]) -> PartialTree<N> {
+        let mut indices: Vec<usize> = indices.iter().copied().filter(|&i| i < self.leaves.len()).collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let height = self.depth();
+        let root = Self::prune(&self.root, height, 0, &indices);
+
+        PartialTree { root, height, indices, odd_node: self.config.odd_node }
+    }
+
+    // `first` is the index of the leftmost leaf under `n`
+    fn prune(n: &Arc<Node<N>>, height: usize, first: usize, indices: &[usize]) -> Arc<Node<N>> {
+        let last = first + (1 << height);
+        if !indices.iter().any(|&i| i >= first && i < last) {
+            let Some(hash) = n.hash() else { return Arc::clone(n) };
+            return Arc::new(Node::Pruned { hash: *hash });
+        }
+
+        match n.as_ref() {
+            Node::Node { hash, left, right } => {
+                let half = first + (1 << (height - 1));
+                let left = Self::prune(left, height - 1, first, indices);
+                let right = match right.as_ref() {
+                    Node::Empty => Arc::clone(right),
+                    _ => Self::prune(right, height - 1, half, indices),
+                };
+
+                Arc::new(Node::Node { hash: *hash, left, right })
+            },
+            _ => Arc::clone(n),
+        }
+    }
+
+    // positions of the leaves that differ between the two trees, leaves only one of them has included.
+    // Subtrees with the same hash are skipped so only the paths to the differences get walked.
+    // Trees hashing their nodes differently have different roots over the same leaves, no list of
+    // leaves can account for that so they are rejected.
+    pub fn diff(&self, other: &Self) -> Result<Vec<usize>, MerkleError> {
+        if self.config != other.config {
+            return Err(MerkleError::ConfigMismatch);
+        }
+        let (shorter, longer) = if self.leaves.len() <= other.leaves.len() { (self, other) } else { (other, self) };
+
+        let mut indices = vec![];
+        if self.depth() == other.depth() {
+            let leaves = (self.leaves.as_slice(), other.leaves.as_slice());
+            Self::diff_nodes(self.root(), other.root(), self.depth(), 0, leaves, &mut indices);
+        } else {
+            // the leaves don't sit under the same nodes, compare them one by one
+            let pairs = shorter.leaves.iter().zip(&longer.leaves).enumerate();
+            indices.extend(pairs.filter(|(_, (a, b))| a.known_hash() != b.known_hash()).map(|(i, _)| i));
+        }
+        indices.extend(shorter.leaves.len()..longer.leaves.len());
+
+        Ok(indices)
+    }
+
+    // `first` is the index of the leftmost leaf under `a` and `b`, `leaves` are the trees' leaves
+    fn diff_nodes(a: &Node<N>, b: &Node<N>, height: usize, first: usize, leaves: (&[Arc<Node<N>>], &[Arc<Node<N>>]), indices: &mut Vec<usize>) {
+        if a.hash() == b.hash() {
+            return;
+        }
+
+        match (a, b) {
+            (Node::Leaf { .. }, Node::Leaf { .. }) => indices.push(first),
+            (Node::Node { left: al, right: ar, .. }, Node::Node { left: bl, right: br, .. }) => {
+                Self::diff_nodes(al, bl, height - 1, first, leaves, indices);
+                Self::diff_nodes(ar, br, height - 1, first + (1 << (height - 1)), leaves, indices);
+            },
+            // the nodes don't line up, e.g. a promoted leaf against a subtree or a missing right
+            // child, so compare the leaves under them one by one. Past the end of the shorter tree
+            // there is nothing to compare, those leaves get added by `diff`.
+            _ => {
+                let end = (first + (1 << height)).min(leaves.0.len()).min(leaves.1.len());
+                indices.extend((first..end).filter(|&i| leaves.0[i].known_hash() != leaves.1[i].known_hash()));
+            },
+        }
+    }
+
+    // (hash, left, right) for every internal node, root first. right is None for the last node of an
+    // odd level: with Duplicate hash is left hashed with itself, with Promote it is left's hash as is.
+    pub fn iter_internal(&self) -> impl Iterator<Item = ([u8; N], [u8; N], Option<[u8; N]>)> + '_ {
+        let mut stack: Vec<&Node<N>> = vec![self.root()];
+        core::iter::from_fn(move || {
+            while let Some(n) = stack.pop() {
+                if let Some(left) = n.get_left() {
+                    let right = n.get_right();
+                    stack.extend(right);
+                    stack.push(left);
+                    return Some((*n.known_hash(), *left.known_hash(), right.map(|r| *r.known_hash())));
+                }
+            }
+            None
+        })
+    }
+
+    // one line per node, children indented under their parent, hashes cut to their first 4 bytes
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+
+        let mut stack: Vec<(&Node<N>, usize)> = vec![(self.root(), 0)];
+        while let Some((n, depth)) = stack.pop() {
+            let line = match n {
+                Node::Empty => String::from("empty"),
+                Node::Node { hash, .. } => format!("node {}", hex::encode(&hash[..4])),
+                Node::Leaf { hash } => format!("leaf {}", hex::encode(&hash[..4])),
+                Node::Pruned { hash } => format!("pruned {}", hex::encode(&hash[..4])),
+            };
+            out.push_str(&"  ".repeat(depth));
+            out.push_str(&line);
+            out.push('\n');
+
+            if let Node::Node { left, right, .. } = n {
+                stack.push((right, depth + 1));
+                stack.push((left, depth + 1));
+            }
+        }
+
+        out
+    }
+
+    // node hashes level by level, leaves first and the root last
+    pub fn levels(&self) -> Vec<Vec<[u8; N]>> {
+        let mut levels = vec![];
+
+        // walk down from the root, Empty children are not counted
+        let mut level: Vec<&Node<N>> = self.leaves.first().map(|_| self.root()).into_iter().collect();
+        while !level.is_empty() {
+            levels.push(level.iter().map(|n| *n.known_hash()).collect());
+            level = level.iter()
+                .flat_map(|n| [n.get_left(), n.get_right()])
+                .flatten()
+                .collect();
+        }
+
+        levels.reverse();
+        levels
+    }
+
+    pub fn level_widths(&self) -> Vec<usize> {
+        self.levels().iter().map(Vec::len).collect()
+    }
+
+    pub fn last_leaf_duplication_levels(&self) -> usize {
+        // the last leaf sits at the end of the rightmost real path
+        let mut levels = 0;
+        let mut n = self.root();
+        while let Some(left) = n.get_left() {
+            match n.get_right() {
+                Some(right) => n = right,
+                None => {
+                    levels += 1;
+                    n = left;
+                }
+            }
+        }
+
+        levels
+    }
+
+    pub fn affected_indices(&self, index: usize) -> Vec<usize> {
+        if index >= self.leaves.len() {
+            return vec![];
+        }
+
+        // every other leaf has an ancestor of `index` as a sibling where their paths meet
+        let mut affected: Vec<usize> = (0..self.leaves.len()).filter(|&i| i != index).collect();
+
+        // our own proof only changes if we got paired with ourselves on the way up, a promoted
+        // node has no sibling at all
+        if self.config.odd_node == OddNodeStrategy::Promote {
+            return affected;
+        }
+        let mut pos = index;
+        let mut width = self.leaves.len();
+        while width > 1 {
+            if pos.is_multiple_of(2) && pos + 1 == width {
+                affected.insert(index, index);
+                break;
+            }
+            pos /= 2;
+            width = width.div_ceil(2);
+        }
+
+        affected
+    }
+
+    pub fn verify(data: Vec<u8>, proofs: Proof<N>) -> [u8; N] {
+        Self::verify_from_leaf_hash(H::hash(&data), proofs)
+    }
+
+    // same as `verify` for callers that only have the leaf hash, like the ones `generate_proofs` takes
+    pub fn verify_from_leaf_hash(leaf_hash: [u8; N], proofs: Proof<N>) -> [u8; N] {
+        let mut hash = leaf_hash;
+
+        for proof in &proofs {
+            if proof.1 == 1 {
+                hash = hash_pair::<H, N>(&hash, &proof.0);
+            } else {
+                hash = hash_pair::<H, N>(&proof.0, &hash);
+            }
+        }
+
+        hash
+    }
+
+    // folds the steps as they come, e.g. straight out of a decoder, without collecting them first
+    pub fn verify_iter<I: IntoIterator<Item = ([u8; N], u8)>>(data: &[u8], steps: I) -> [u8; N] {
+        steps.into_iter().fold(H::hash(data), |hash, (sibling, direction)| {
+            if direction == 1 {
+                hash_pair::<H, N>(&hash, &sibling)
+            } else {
+                hash_pair::<H, N>(&sibling, &hash)
+            }
+        })
+    }
+
+    // `verify` reads anything but 1 as a left sibling, this rejects direction bytes other than 0 and 1
+    pub fn try_verify(data: Vec<u8>, proofs: Proof<N>) -> Result<[u8; N], MerkleError> {
+        if proofs.iter().any(|(_, direction)| *direction > 1) {
+            return Err(MerkleError::MalformedProof);
+        }
+
+        Ok(Self::verify(data, proofs))
+    }
+
+    // refuses to hash anything for a proof deeper than `max_depth`, usually `depth()` of the tree
+    pub fn verify_with_max_depth(data: Vec<u8>, proofs: Proof<N>, max_depth: usize) -> Result<[u8; N], MerkleError> {
+        if proofs.len() > max_depth {
+            return Err(MerkleError::ProofTooLong);
+        }
+
+        Ok(Self::verify(data, proofs))
+    }
+
+    pub fn verify_truncated(data: Vec<u8>, proofs: Proof<N>, t: usize) -> [u8; N] {
+        let mut hash = H::hash(&data);
+
+        for proof in &proofs {
+            if proof.1 == 1 {
+                hash = truncate_hash(hash_pair::<H, N>(&hash, &proof.0), t);
+            } else {
+                hash = truncate_hash(hash_pair::<H, N>(&proof.0, &hash), t);
+            }
+        }
+
+        hash
+    }
+
+    // counterpart of `new_sorted`, the siblings go from the leaf up and carry no direction, so a
+    // bare witness like the one Solidity's `MerkleProof.verify` takes has none that can be wrong.
+    // A sibling equal to the running hash (a duplicated odd node) hashes as hash(a || a).
+    #[doc(alias = "verify_sorted_witness")]
+    pub fn verify_sorted(leaf_hash: [u8; N], siblings: &[[u8; N]], root: &[u8; N]) -> bool {
+        let hash = siblings.iter().fold(leaf_hash, |hash, sibling| {
+            if &hash <= sibling {
+                hash_pair::<H, N>(&hash, sibling)
+            } else {
+                hash_pair::<H, N>(sibling, &hash)
+            }
+        });
+
+        roots_equal(&hash, root)
+    }
+
+    // counterpart of `new_with_domain_separation`, `data` is hashed as a leaf with the 0x00 prefix
+    pub fn verify_with_domain_separation(data: Vec<u8>, proofs: Proof<N>) -> [u8; N] {
+        let config = TreeConfig { domain_separation: true, ..TreeConfig::default() };
+        let mut hash = Self::domain_separated_leaf(&data);
+
+        for proof in &proofs {
+            if proof.1 == 1 {
+                hash = Self::hash_children(&hash, &proof.0, &config);
+            } else {
+                hash = Self::hash_children(&proof.0, &hash, &config);
+            }
+        }
+
+        hash
+    }
+
+    // folds the proof from a leaf hash, the direction bytes give back the bits of the leaf index
+    pub fn verify_with_position(leaf_hash: [u8; N], proofs: &[([u8; N], u8)]) -> ([u8; N], usize) {
+        let mut hash = leaf_hash;
+        let mut index = 0;
+
+        for (level, (sibling, direction)) in proofs.iter().enumerate() {
+            if *direction == 1 {
+                hash = hash_pair::<H, N>(&hash, sibling);
+            } else {
+                // sibling on the left means we are a right child
+                hash = hash_pair::<H, N>(sibling, &hash);
+                index |= 1 << level;
+            }
+        }
+
+        (hash, index)
+    }
+
+    // no direction bytes, index and leaf_count tell us where we are at every level. Levels where
+    // we get paired with ourselves have no sibling in `siblings`.
+    pub fn verify_with_width(leaf_hash: [u8; N], siblings: &[[u8; N]], index: usize, leaf_count: usize, root: &[u8; N]) -> bool {
+        if index >= leaf_count {
+            return false;
+        }
+
+        let mut hash = leaf_hash;
+        let mut siblings = siblings.iter();
+        let mut pos = index;
+        let mut width = leaf_count;
+        while width > 1 {
+            if pos % 2 == 1 {
+                let Some(sibling) = siblings.next() else { return false };
+                hash = hash_pair::<H, N>(sibling, &hash);
+            } else if pos + 1 == width {
+                hash = hash_pair::<H, N>(&hash, &hash);
+            } else {
+                let Some(sibling) = siblings.next() else { return false };
+                hash = hash_pair::<H, N>(&hash, sibling);
+            }
+
+            pos /= 2;
+            width = width.div_ceil(2);
+        }
+
+        siblings.next().is_none() && roots_equal(&hash, root)
+    }
+
+    // siblings as `generate_proof_by_index` gives them minus the direction bytes, one per level.
+    // Unlike `verify_with_width` a leaf paired with itself keeps that level, its sibling has to
+    // be the running hash.
+    pub fn verify_by_index(leaf_hash: [u8; N], index: usize, siblings: &[[u8; N]], total_leaves: usize, root: &[u8; N]) -> bool {
+        if index >= total_leaves {
+            return false;
+        }
+
+        let mut hash = leaf_hash;
+        let mut siblings = siblings.iter();
+        let mut pos = index;
+        let mut width = total_leaves;
+        while width > 1 {
+            let Some(sibling) = siblings.next() else { return false };
+            if pos % 2 == 1 {
+                hash = hash_pair::<H, N>(sibling, &hash);
+            } else if pos + 1 == width {
+                if sibling != &hash {
+                    return false;
+                }
+                hash = hash_pair::<H, N>(&hash, &hash);
+            } else {
+                hash = hash_pair::<H, N>(&hash, sibling);
+            }
+
+            pos /= 2;
+            width = width.div_ceil(2);
+        }
+
+        siblings.next().is_none() && roots_equal(&hash, root)
+    }
+
+    // direction bytes are the bits of the leaf position, least significant first,
+    // so a proof that checks out also tells where the leaf sits
+    pub fn verify_any_index(leaf_hash: [u8; N], proofs: &[([u8; N], u8)], root: &[u8; N]) -> Option<usize> {
+        let (computed_root, index) = Self::verify_with_position(leaf_hash, proofs);
+
+        roots_equal(&computed_root, root).then_some(index)
+    }
+
+    #[deprecated(note = "use `verify_any_index`")]
+    pub fn verify_and_locate(leaf_hash: [u8; N], proofs: &[([u8; N], u8)], root: &[u8; N]) -> Option<usize> {
+        Self::verify_any_index(leaf_hash, proofs, root)
+    }
+
+    pub fn verify_against_root(data: Vec<u8>, proofs: Proof<N>, expected_root: &[u8; N]) -> bool {
+        roots_equal(&Self::verify(data, proofs), expected_root)
+    }
+
+    // never allocates: each step is hashed from a stack buffer borrowing nothing but the proof
+    pub fn verify_zerocopy(leaf_hash: &[u8; N], proofs: &[([u8; N], u8)], root: &[u8; N]) -> bool {
+        let mut hash = *leaf_hash;
+
+        for (sibling, direction) in proofs {
+            hash = if *direction == 1 {
+                hash_pair::<H, N>(&hash, sibling)
+            } else {
+                hash_pair::<H, N>(sibling, &hash)
+            };
+        }
+
+        roots_equal(&hash, root)
+    }
+
+    // `sized_root` is what `sized_root()` returned for a tree of `leaf_count` leaves
+    pub fn verify_sized(data: &[u8], proofs: &[([u8; N], u8)], sized_root: &[u8; N], leaf_count: usize) -> bool {
+        let root = Self::verify(data.to_vec(), proofs.to_vec());
+
+        roots_equal(&Self::size_commitment(&root, leaf_count), sized_root)
+    }
+
+    // the root is read from `header_bytes[root_offset..root_offset + N]`, like a block header would carry it
+    pub fn verify_in_header(data: &[u8], proofs: &[([u8; N], u8)], header_bytes: &[u8], root_offset: usize) -> bool {
+        let Some(root) = root_offset.checked_add(N).and_then(|end| header_bytes.get(root_offset..end)) else {
+            return false;
+        };
+
+        roots_equal(&Self::verify(data.to_vec(), proofs.to_vec()), root)
+    }
+
+    pub fn verify_root_hex(data: &[u8], proofs: &[([u8; N], u8)], root_hex: &str) -> Result<bool, MerkleError> {
+        let mut root = [0u8; N];
+        let root_hex = root_hex.strip_prefix("0x").unwrap_or(root_hex);
+        hex::decode_to_slice(root_hex, &mut root).map_err(|_| MerkleError::InvalidHex)?;
+
+        Ok(roots_equal(&Self::verify(data.to_vec(), proofs.to_vec()), &root))
+    }
+
+    #[deprecated(note = "use `verify_root_hex`")]
+    pub fn verify_hex(data: Vec<u8>, proofs: Proof<N>, root_hex: &str) -> Result<bool, MerkleError> {
+        Self::verify_root_hex(&data, &proofs, root_hex)
+    }
+
+    // stricter than `verify_zerocopy`: a proof with an all zero sibling is reported as Malformed
+    // even when it folds to the root, e.g. the proof next to a leaf that was inserted as [0; 32]
+    pub fn verify_detailed(data: &[u8], proofs: &[([u8; N], u8)], root: &[u8; N]) -> VerifyOutcome<N> {
+        for (sibling, direction) in proofs {
+            if *direction > 1 {
+                return VerifyOutcome::Malformed { reason: "direction byte must be 0 or 1" };
+            }
+            // no hash function gives all zeros in practice, such a sibling is the placeholder an
+            // encoder left for a missing node
+            if sibling == &[0u8; N] {
+                return VerifyOutcome::Malformed { reason: "zero sibling hash" };
+            }
+        }
+
+        let computed_root = Self::verify(data.to_vec(), proofs.to_vec());
+        match computed_root.iter().zip(root).position(|(a, b)| a != b) {
+            None => VerifyOutcome::Valid,
+            Some(first_mismatch) => VerifyOutcome::Invalid { computed_root, first_mismatch },
+        }
+    }
+
+    // `verify` along with how many steps were hashed in
+    pub fn verify_with_info(data: Vec<u8>, proofs: Proof<N>) -> VerifyResult<N> {
+        let steps_applied = proofs.len();
+
+        VerifyResult { computed_root: Self::verify(data, proofs), steps_applied }
+    }
+
+    pub fn reconstruct_from_proofs(leaf_hashes: &[[u8; N]], proofs: &[Proof<N>]) -> Result<Self, MerkleError> {
+        if leaf_hashes.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+        if leaf_hashes.len() != proofs.len() {
+            return Err(MerkleError::ProofCountMismatch);
+        }
+
+        // every node we learn about is keyed by (level, position), leaves being level 0
+        let mut known: BTreeMap<(usize, usize), [u8; N]> = BTreeMap::new();
+        for (i, leaf) in leaf_hashes.iter().enumerate() {
+            known.insert((0, i), *leaf);
+        }
+
+        let mut record = |key: (usize, usize), hash: [u8; N]| -> Result<(), MerkleError> {
+            match known.get(&key) {
+                Some(h) if h != &hash => Err(MerkleError::InconsistentProofs),
+                _ => {
+                    known.insert(key, hash);
+                    Ok(())
+                }
+            }
+        };
+
+        let mut root: Option<[u8; N]> = None;
+        for (i, proof) in proofs.iter().enumerate() {
+            let mut hash = leaf_hashes[i];
+            let mut pos = i;
+
+            for (level, (sibling, direction)) in proof.iter().enumerate() {
+                // direction tells us on which side the sibling is, it has to match our position
+                match (direction, pos % 2) {
+                    (1, 0) => hash = hash_pair::<H, N>(&hash, sibling),
+                    (0, 1) => hash = hash_pair::<H, N>(sibling, &hash),
+                    _ => return Err(MerkleError::MalformedProof),
+                }
+                record((level, pos ^ 1), *sibling)?;
+
+                pos /= 2;
+                record((level + 1, pos), hash)?;
+            }
+
+            match root {
+                Some(r) if r != hash => return Err(MerkleError::InconsistentProofs),
+                _ => root = Some(hash),
+            }
+        }
+
+        let tree = Self::new(leaf_hashes.to_vec());
+        if Some(*tree.root_hash()) != root {
+            return Err(MerkleError::InconsistentProofs);
+        }
+
+        Ok(tree)
+    }
+}
+
+impl<H: MerkleHasher<N>, const N: usize> FromIterator<[u8; N]> for GenericMerkleTree<H, N> {
+    fn from_iter<I: IntoIterator<Item = [u8; N]>>(iter: I) -> Self {
+        Self::build(iter, TreeConfig::default())
+    }
+}
+
+// nodes have no parent links so a clone can share all of them, mutations path copy anyway
+impl<H: MerkleHasher<N>, const N: usize> Clone for GenericMerkleTree<H, N> {
+    fn clone(&self) -> Self {
+        Self {
+            root: Arc::clone(&self.root),
+            leaves: self.leaves.clone(),
+            leaf_index: self.leaf_index.clone(),
+            original_order: self.original_order.clone(),
+            config: self.config,
+            hasher: PhantomData,
+        }
+    }
+}
+
+// same leaves in the same order hashed the same way, which means the same root
+impl<H: MerkleHasher<N>, const N: usize> PartialEq for GenericMerkleTree<H, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.config == other.config && self.leaf_hashes_iter().eq(other.leaf_hashes_iter())
+    }
+}
+
+impl<H: MerkleHasher<N>, const N: usize> Eq for GenericMerkleTree<H, N> {}
+
+// internal hashes computed by `new_cached`, to be handed to the next build. Entries are never
+// evicted, `clear` it once it outgrows its use.
+pub struct MerkleTreeCache<H: MerkleHasher<N> = Sha256Hasher, const N: usize = 32> {
+    parents: ParentMap<N>,
+    hasher: PhantomData<fn() -> H>,
+}
+
+impl<H: MerkleHasher<N>, const N: usize> MerkleTreeCache<H, N> {
+    pub fn new() -> Self {
+        Self { parents: ParentMap::new(), hasher: PhantomData }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.parents.clear();
+    }
+}
+
+impl<H: MerkleHasher<N>, const N: usize> Default for MerkleTreeCache<H, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// one place to pick the hashing rules, the tree and its verifier then share them
+#[derive(Debug, Clone, Copy)]
+pub struct MerkleTreeBuilder<H: MerkleHasher<N> = Sha256Hasher, const N: usize = 32> {
+    config: TreeConfig,
+    hasher: PhantomData<fn() -> H>,
+}
+
+impl MerkleTreeBuilder<Sha256Hasher> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// `hasher` keeps the width, a builder for another width starts from here
+impl<H: MerkleHasher<N>, const N: usize> Default for MerkleTreeBuilder<H, N> {
+    fn default() -> Self {
+        Self { config: TreeConfig::default(), hasher: PhantomData }
+    }
+}
+
+impl<H: MerkleHasher<N>, const N: usize> MerkleTreeBuilder<H, N> {
+    pub fn sorted_pairs(mut self, sorted_pairs: bool) -> Self {
+        self.config.sorted_pairs = sorted_pairs;
+        self
+    }
+
+    pub fn domain_separation(mut self, domain_separation: bool) -> Self {
+        self.config.domain_separation = domain_separation;
+        self
+    }
+
+    pub fn truncate(mut self, t: usize) -> Self {
+        self.config.truncate = Some(t);
+        self
+    }
+
+    pub fn odd_node(mut self, strategy: OddNodeStrategy) -> Self {
+        self.config.odd_node = strategy;
+        self
+    }
+
+    pub fn hasher<H2: MerkleHasher<N>>(self) -> MerkleTreeBuilder<H2, N> {
+        MerkleTreeBuilder { config: self.config, hasher: PhantomData }
+    }
+
+    pub fn verifier(&self) -> Verifier<H, N> {
+        Verifier { config: self.config, hasher: PhantomData }
+    }
+
+    // with domain separation the leaves have to come from `Verifier::hash_leaf`
+    pub fn build(&self, leaves: Vec<[u8; N]>) -> Result<GenericMerkleTree<H, N>, MerkleError> {
+        let truncate = checked_truncation::<N>(self.config.truncate.unwrap_or(N))?;
+        if leaves.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+
+        Ok(GenericMerkleTree::build(leaves, TreeConfig { truncate, ..self.config }))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Verifier<H: MerkleHasher<N> = Sha256Hasher, const N: usize = 32> {
+    config: TreeConfig,
+    hasher: PhantomData<fn() -> H>,
+}
+
+impl<H: MerkleHasher<N>, const N: usize> Verifier<H, N> {
+    pub fn hash_leaf(&self, data: &[u8]) -> [u8; N] {
+        if self.config.domain_separation {
+            GenericMerkleTree::<H, N>::domain_separated_leaf(data)
+        } else {
+            H::hash(data)
+        }
+    }
+
+    // directions are ignored for sorted pairs, the pair order comes from the hashes
+    pub fn verify(&self, leaf_hash: [u8; N], proofs: &[([u8; N], u8)], root: &[u8; N]) -> bool {
+        let hash = proofs.iter().fold(leaf_hash, |hash, (sibling, direction)| {
+            if *direction == 1 {
+                GenericMerkleTree::<H, N>::hash_children(&hash, sibling, &self.config)
+            } else {
+                GenericMerkleTree::<H, N>::hash_children(sibling, &hash, &self.config)
+            }
+        });
+
+        roots_equal(&hash, root)
+    }
+
+    // RFC 6962 style check that the tree of `new_size` leaves starts with the `old_size` leaves of
+    // the old one. Both roots get folded from the path of the last old leaf: left siblings are
+    // complete subtrees both trees share, the old tree pairs the path with itself where the new
+    // one may have a right sibling. Trees promoting their odd nodes have no consistency proofs.
+    pub fn verify_consistency(&self, old_root: &[u8; N], new_root: &[u8; N], old_size: usize, new_size: usize, proof: &[[u8; N]]) -> bool {
+        if self.config.odd_node == OddNodeStrategy::Promote || old_size == 0 || old_size > new_size {
+            return false;
+        }
+        if old_size == new_size {
+            return proof.is_empty() && roots_equal(old_root, new_root);
+        }
+
+        let level = old_size.trailing_zeros() as usize;
+        let mut proof = proof.iter();
+        let seed = if old_size.is_power_of_two() {
+            *old_root
+        } else {
+            let Some(seed) = proof.next() else { return false };
+            *seed
+        };
+
+        let (mut old_hash, mut new_hash) = (seed, seed);
+        let mut pos = (old_size - 1) >> level;
+        let mut old_width = old_size >> level;
+        let mut new_width = new_size.div_ceil(1 << level);
+        while new_width > 1 {
+            if pos % 2 == 1 {
+                let Some(sibling) = proof.next() else { return false };
+                new_hash = GenericMerkleTree::<H, N>::hash_children(sibling, &new_hash, &self.config);
+                if old_width > 1 {
+                    old_hash = GenericMerkleTree::<H, N>::hash_children(sibling, &old_hash, &self.config);
+                }
+            } else {
+                // the path is always the last node of the old tree's level
+                if old_width > 1 {
+                    old_hash = GenericMerkleTree::<H, N>::hash_children(&old_hash, &old_hash, &self.config);
+                }
+                if pos + 1 == new_width {
+                    new_hash = GenericMerkleTree::<H, N>::hash_children(&new_hash, &new_hash, &self.config);
+                } else {
+                    let Some(sibling) = proof.next() else { return false };
+                    new_hash = GenericMerkleTree::<H, N>::hash_children(&new_hash, sibling, &self.config);
+                }
+            }
+
+            pos /= 2;
+            old_width = old_width.div_ceil(2);
+            new_width = new_width.div_ceil(2);
+        }
+
+        proof.next().is_none() && roots_equal(&old_hash, old_root) && roots_equal(&new_hash, new_root)
+    }
+}
+
+// only the leaves and how they were hashed get serialized, internal nodes are rebuilt on load
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedTree {
+    algorithm: String,
+    truncate: usize,
+    domain_separation: bool,
+    #[serde(default)]
+    sorted_pairs: bool,
+    #[serde(default)]
+    odd_node: OddNodeStrategy,
+    leaves: Vec<String>,
+    original_order: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl<H: MerkleHasher<N>, const N: usize> serde::Serialize for GenericMerkleTree<H, N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedTree {
+            algorithm: H::ALGORITHM_ID.into(),
+            truncate: self.truncation(),
+            domain_separation: self.config.domain_separation,
+            sorted_pairs: self.config.sorted_pairs,
+            odd_node: self.config.odd_node,
+            leaves: self.leaves.iter().map(|l| hex::encode(l.known_hash())).collect(),
+            original_order: self.original_order.clone(),
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, H: MerkleHasher<N>, const N: usize> serde::Deserialize<'de> for GenericMerkleTree<H, N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        let tree = SerializedTree::deserialize(deserializer)?;
+        if tree.algorithm != H::ALGORITHM_ID {
+            return Err(D::Error::custom(format!("tree was built with {}, expected {}", tree.algorithm, H::ALGORITHM_ID)));
+        }
+        let truncate = checked_truncation::<N>(tree.truncate).map_err(D::Error::custom)?;
+        // every leaf position exactly once, `original_index` and `leaf_hashes` index with it
+        let mut seen = vec![false; tree.leaves.len()];
+        let is_permutation = tree.original_order.len() == tree.leaves.len()
+            && tree.original_order.iter().all(|&i| i < seen.len() && !core::mem::replace(&mut seen[i], true));
+        if !is_permutation {
+            return Err(D::Error::custom("original order doesn't cover the leaves"));
+        }
+
+        let leaves = tree.leaves.iter().map(|leaf| {
+            let mut hash = [0u8; N];
+            hex::decode_to_slice(leaf, &mut hash).map(|_| hash).map_err(|_| D::Error::custom(MerkleError::InvalidHex))
+        }).collect::<Result<Vec<[u8; N]>, D::Error>>()?;
+
+        let config = TreeConfig {
+            truncate,
+            domain_separation: tree.domain_separation,
+            sorted_pairs: tree.sorted_pairs,
+            odd_node: tree.odd_node,
+        };
+        let mut merkle_tree = Self::build(leaves, config);
+        merkle_tree.original_order = tree.original_order;
+
+        Ok(merkle_tree)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofStep<const N: usize = 32> {
+    pub sibling: [u8; N],
+    // the sibling gets hashed on the right of the running hash
+    pub is_right_sibling: bool,
+}
+
+// a proof bound to the leaf it proves, steps go from the leaf up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof<const N: usize = 32> {
+    pub steps: Vec<ProofStep<N>>,
+    pub leaf_hash: [u8; N],
+}
+
+impl MerkleProof {
+    pub fn verify(&self, expected_root: &[u8; 32]) -> bool {
+        self.verify_with::<Sha256Hasher>(expected_root)
+    }
+
+    pub fn verify_data(&self, data: &[u8], expected_root: &[u8; 32]) -> Result<(), MerkleError> {
+        self.verify_data_with::<Sha256Hasher>(data, expected_root)
+    }
+}
+
+impl<const N: usize> MerkleProof<N> {
+    pub fn from_tuples(leaf_hash: [u8; N], proofs: &[([u8; N], u8)]) -> Self {
+        let steps = proofs.iter().map(|(sibling, direction)| ProofStep { sibling: *sibling, is_right_sibling: *direction == 1 }).collect();
+
+        Self { steps, leaf_hash }
+    }
+
+    // the `(sibling, direction)` layout the rest of the crate takes
+    pub fn into_tuples(self) -> Proof<N> {
+        self.steps.iter().map(|step| (step.sibling, step.is_right_sibling as u8)).collect()
+    }
+
+    // checks the data is the leaf this proof was made for before checking the root
+    pub fn verify_data_with<H: MerkleHasher<N>>(&self, data: &[u8], expected_root: &[u8; N]) -> Result<(), MerkleError> {
+        if H::hash(data) != self.leaf_hash {
+            return Err(MerkleError::LeafMismatch);
+        }
+        if !self.verify_with::<H>(expected_root) {
+            return Err(MerkleError::RootMismatch);
+        }
+
+        Ok(())
+    }
+
+    pub fn verify_with<H: MerkleHasher<N>>(&self, expected_root: &[u8; N]) -> bool {
+        let root = self.steps.iter().fold(self.leaf_hash, |hash, step| {
+            if step.is_right_sibling {
+                hash_pair::<H, N>(&hash, &step.sibling)
+            } else {
+                hash_pair::<H, N>(&step.sibling, &hash)
+            }
+        });
+
+        roots_equal(&root, expected_root)
+    }
+}
+
+// folds proof steps as they arrive, from the leaf up, without holding the whole proof
+#[derive(Debug, Clone)]
+pub struct ProofVerifier<H: MerkleHasher<N> = Sha256Hasher, const N: usize = 32> {
+    hash: [u8; N],
+    hasher: PhantomData<fn() -> H>,
+}
+
+impl ProofVerifier<Sha256Hasher> {
+    pub fn new(leaf_hash: [u8; 32]) -> Self {
+        Self::with_hasher(leaf_hash)
+    }
+}
+
+impl<H: MerkleHasher<N>, const N: usize> ProofVerifier<H, N> {
+    pub fn with_hasher(leaf_hash: [u8; N]) -> Self {
+        Self { hash: leaf_hash, hasher: PhantomData }
+    }
+
+    pub fn push_step(&mut self, sibling: [u8; N], is_right: bool) {
+        self.hash = if is_right {
+            hash_pair::<H, N>(&self.hash, &sibling)
+        } else {
+            hash_pair::<H, N>(&sibling, &self.hash)
+        };
+    }
+
+    // the root the steps so far lead to
+    pub fn finalize(self) -> [u8; N] {
+        self.hash
+    }
+}
+
+// a tree keeping only what is needed to prove some of its leaves, everything else is Pruned
+pub struct PartialTree<const N: usize = 32> {
+    root: Arc<Node<N>>,
+    height: usize,
+    indices: Vec<usize>,
+    odd_node: OddNodeStrategy,
+}
+
+impl<const N: usize> PartialTree<N> {
+    pub fn root_hash(&self) -> &[u8; N] {
+        self.root.hash().unwrap_or(&[0u8; N])
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    pub fn generate_proof(&self, index: usize) -> Result<Proof<N>, MerkleError> {
+        if self.indices.binary_search(&index).is_err() {
+            return Err(MerkleError::LeafNotFound);
+        }
+
+        Ok(self.root.gen_proof(self.height, index, self.odd_node))
+    }
+}
+
+// proves several leaves at once, each sibling is only carried once. `combine_proofs` makes them
+// for the default hashing rules, `generate_multiproof` for the rules of the tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiProof<const N: usize = 32> {
+    pub indices: Vec<usize>,
+    pub leaf_count: usize,
+    pub height: usize,
+    // sibling hashes keyed by (level, position), leaves being level 0
+    pub nodes: BTreeMap<(usize, usize), [u8; N]>,
+    // how the tree it came from hashes its nodes
+    config: TreeConfig,
+}
+
+impl MultiProof {
+    // `leaves` are the hashes of the leaves at `self.indices`, in the same order
+    pub fn verify(&self, leaves: &[[u8; 32]], root: &[u8; 32]) -> bool {
+        self.verify_with::<Sha256Hasher>(leaves, root)
+    }
+}
+
+impl<const N: usize> MultiProof<N> {
+    // number of sibling hashes carried
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // for proofs out of a `GenericMerkleTree<H>`
+    pub fn verify_with<H: MerkleHasher<N>>(&self, leaves: &[[u8; N]], root: &[u8; N]) -> bool {
+        if leaves.len() != self.indices.len() {
+            return false;
+        }
+
+        let leaves = self.indices.iter().copied().zip(leaves.iter().copied());
+        verify_nodes::<H, N>(leaves, self.leaf_count, self.height, &self.nodes, &self.config, root)
+    }
+}
+
+// proves the contiguous leaves `start..end`, the shape of a `MultiProof` over that range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeProof<const N: usize = 32> {
+    pub start: usize,
+    pub end: usize,
+    pub leaf_count: usize,
+    pub height: usize,
+    // sibling hashes keyed by (level, position), leaves being level 0
+    pub nodes: BTreeMap<(usize, usize), [u8; N]>,
+    // how the tree it came from hashes its nodes
+    config: TreeConfig,
+}
+
+impl RangeProof {
+    // `leaves` are the hashes of the leaves from `self.start` to `self.end`, in order
+    pub fn verify(&self, leaves: &[[u8; 32]], root: &[u8; 32]) -> bool {
+        self.verify_with::<Sha256Hasher>(leaves, root)
+    }
+}
+
+impl<const N: usize> RangeProof<N> {
+    // number of sibling hashes carried
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // for proofs out of a `GenericMerkleTree<H>`
+    pub fn verify_with<H: MerkleHasher<N>>(&self, leaves: &[[u8; N]], root: &[u8; N]) -> bool {
+        if leaves.len() != self.end.saturating_sub(self.start) {
+            return false;
+        }
+
+        let leaves = (self.start..self.end).zip(leaves.iter().copied());
+        verify_nodes::<H, N>(leaves, self.leaf_count, self.height, &self.nodes, &self.config, root)
+    }
+}
+
+// folds the proven leaves, given by position, up to the root pulling the missing siblings from `nodes`.
+// The last node of an odd width level has no sibling in `nodes`, it gets paired with itself.
+fn verify_nodes<H: MerkleHasher<N>, const N: usize>(
+    leaves: impl Iterator<Item = (usize, [u8; N])>,
+    leaf_count: usize,
+    height: usize,
+    nodes: &BTreeMap<(usize, usize), [u8; N]>,
+    config: &TreeConfig,
+    root: &[u8; N],
+) -> bool {
+    let mut level: BTreeMap<usize, [u8; N]> = leaves.collect();
+    if level.is_empty() || level.keys().any(|&pos| pos >= leaf_count) {
+        return false;
+    }
+
+    let mut width = leaf_count;
+    for h in 0..height {
+        let mut next = BTreeMap::new();
+        for &pos in level.keys() {
+            let parent = pos / 2;
+            if next.contains_key(&parent) {
+                continue;
+            }
+
+            let lookup = |p: usize| level.get(&p).or_else(|| nodes.get(&(h, p)));
+            let Some(left) = lookup(2 * parent) else { return false };
+            let right = if 2 * parent + 1 == width { Some(left) } else { lookup(2 * parent + 1) };
+            let Some(right) = right else { return false };
+            next.insert(parent, GenericMerkleTree::<H, N>::hash_children(left, right, config));
+        }
+        level = next;
+        width = width.div_ceil(2);
+    }
+
+    level.len() == 1 && level.get(&0).is_some_and(|hash| roots_equal(hash, root))
+}
+
+// `proofs` come from a tree of `leaf_count` leaves
+pub fn combine_proofs<const N: usize>(proofs: &[(usize, Proof<N>)], leaf_count: usize) -> MultiProof<N> {
+    let mut indices: Vec<usize> = proofs.iter().map(|(index, _)| *index).collect();
+    indices.sort_unstable();
+    indices.dedup();
+
+    let mut nodes = BTreeMap::new();
+    let mut height = 0;
+    for (index, proof) in proofs {
+        let mut width = leaf_count;
+        for (level, (sibling, _)) in proof.iter().enumerate() {
+            let pos = index >> level;
+            // a node paired with itself is its own sibling, the verifier can tell from the width
+            if pos ^ 1 < width {
+                nodes.entry((level, pos ^ 1)).or_insert(*sibling);
+            }
+            width = width.div_ceil(2);
+        }
+        height = height.max(proof.len());
+    }
+
+    // siblings on the path of another proven leaf get recomputed by the verifier, no need to ship them
+    for index in &indices {
+        for level in 0..height {
+            nodes.remove(&(level, index >> level));
+        }
+    }
+
+    MultiProof { indices, leaf_count, height, nodes, config: TreeConfig::default() }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+// which half of the tree the proven leaf is in, None for a single leaf tree with nothing to prove
+pub fn proof_subtree_side(proofs: &[([u8; 32], u8)]) -> Option<Side> {
+    // the last step is the sibling right under the root
+    proofs.last().map(|(_, direction)| if *direction == 1 { Side::Left } else { Side::Right })
+}
+
+// internal node hashes building a tree of `leaf_count` leaves takes, an odd node out is hashed
+// with itself so every level of width w costs ceil(w / 2). Verifying a proof takes one per step.
+pub fn construction_hash_count(leaf_count: usize) -> usize {
+    let mut count = 0;
+    let mut width = leaf_count;
+    while width > 1 {
+        width = width.div_ceil(2);
+        count += width;
+    }
+
+    count
+}
+
+pub fn proof_byte_size(proofs: &[([u8; 32], u8)]) -> usize {
+    proofs.len() * PROOF_STEP_SIZE
+}
+
+// steps are laid out back to back, from the leaf up, PROOF_STEP_SIZE bytes each
+pub fn encode_proof(proofs: &[([u8; 32], u8)]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(proof_byte_size(proofs));
+    for (sibling, direction) in proofs {
+        bytes.extend_from_slice(sibling);
+        bytes.push(*direction);
+    }
+
+    bytes
+}
+
+pub fn decode_proof(bytes: &[u8]) -> Result<Proof, MerkleError> {
+    if !bytes.len().is_multiple_of(PROOF_STEP_SIZE) {
+        return Err(MerkleError::MalformedProof);
+    }
+
+    bytes.chunks_exact(PROOF_STEP_SIZE).map(|step| {
+        let (sibling, direction) = step.split_at(32);
+        match direction[0] {
+            d @ (0 | 1) => Ok((sibling.try_into().unwrap(), d)),
+            _ => Err(MerkleError::MalformedProof),
+        }
+    }).collect()
+}
+
+// directions packed into a leading bitmap, step i being bit i % 8 of byte i / 8, then the siblings
+pub fn encode_proof_compact(proofs: &[([u8; 32], u8)]) -> Vec<u8> {
+    let mut bytes = vec![0u8; proofs.len().div_ceil(8)];
+    for (i, (_, direction)) in proofs.iter().enumerate() {
+        bytes[i / 8] |= (*direction & 1) << (i % 8);
+    }
+    for (sibling, _) in proofs {
+        bytes.extend_from_slice(sibling);
+    }
+
+    bytes
+}
+
+pub fn decode_proof_compact(bytes: &[u8]) -> Result<Proof, MerkleError> {
+    // the length grows strictly with the step count, at most one count fits
+    let steps = (bytes.len() / 33..=bytes.len() / 32)
+        .find(|steps| steps * 32 + steps.div_ceil(8) == bytes.len())
+        .ok_or(MerkleError::MalformedProof)?;
+    let (bitmap, siblings) = bytes.split_at(steps.div_ceil(8));
+
+    // padding bits past the last step have to be zero, so each proof has a single encoding
+    if steps % 8 != 0 && bitmap[steps / 8] >> (steps % 8) != 0 {
+        return Err(MerkleError::MalformedProof);
+    }
+
+    Ok(siblings.chunks_exact(32).enumerate().map(|(i, sibling)| {
+        (sibling.try_into().unwrap(), (bitmap[i / 8] >> (i % 8)) & 1)
+    }).collect())
+}
+
+// builds the tree just long enough to get the root and one proof out of it
+pub fn commit_and_prove(leaves: Vec<[u8; 32]>, target_index: usize) -> Result<([u8; 32], Proof), MerkleError> {
+    if leaves.is_empty() {
+        return Err(MerkleError::EmptyTree);
+    }
+
+    let tree = MerkleTree::new(leaves);
+    let proofs = tree.generate_proof_by_index(target_index)?;
+
+    Ok((*tree.root_hash(), proofs))
+}
+
+// verifying runs this once per proof step
+pub(crate) fn hash_pair<H: MerkleHasher<N>, const N: usize>(left: &[u8; N], right: &[u8; N]) -> [u8; N] {
+    hash_concat::<H, N>(None, left, right)
+}
+
+// hashes prefix || left || right from a stack buffer, only hashes wider than 64 bytes need the heap
+fn hash_concat<H: MerkleHasher<N>, const N: usize>(prefix: Option<u8>, left: &[u8; N], right: &[u8; N]) -> [u8; N] {
+    let len = 1 + 2 * N;
+    let mut stack = [0u8; 1 + 2 * 64];
+    let mut heap = Vec::new();
+    let buf = if len <= stack.len() {
+        &mut stack[..len]
+    } else {
+        heap.resize(len, 0);
+        &mut heap[..]
+    };
+    buf[0] = prefix.unwrap_or_default();
+    buf[1..1 + N].copy_from_slice(left);
+    buf[1 + N..].copy_from_slice(right);
+    let start = if prefix.is_some() { 0 } else { 1 };
+
+    H::hash(&buf[start..])
+}
+
+// a cut at the full width keeps every byte, stored as None so both spellings give equal configs
+fn checked_truncation<const N: usize>(t: usize) -> Result<Option<usize>, MerkleError> {
+    match t {
+        0 => Err(MerkleError::InvalidTruncation),
+        t if t > N => Err(MerkleError::InvalidTruncation),
+        t if t == N => Ok(None),
+        t => Ok(Some(t)),
+    }
+}
+
+// with the constant-time feature every byte gets compared whatever the first difference is,
+// so the time taken doesn't tell how much of a forged root was right
+#[cfg(feature = "constant-time")]
+pub(crate) fn roots_equal(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | core::hint::black_box(x ^ y));
+    core::hint::black_box(diff) == 0
+}
+
+#[cfg(not(feature = "constant-time"))]
+pub(crate) fn roots_equal(a: &[u8], b: &[u8]) -> bool {
+    a == b
+}
+
+fn truncate_hash<const N: usize>(mut hash: [u8; N], t: usize) -> [u8; N] {
+    hash[t.min(N)..].fill(0);
+    hash
+}
+
+// for light clients holding only the data, a proof and a trusted root, no tree type needed
+pub fn verify_proof(leaf_data: &[u8], proofs: &[([u8; 32], u8)], root: &[u8; 32]) -> bool {
+    MerkleTree::verify_zerocopy(&Sha256Hasher::hash(leaf_data), proofs, root)
+}
+
+// `Verifier::verify_consistency` for trees built like `MerkleTree::new`
+pub fn verify_consistency(old_root: &[u8; 32], new_root: &[u8; 32], old_size: usize, new_size: usize, proof: &[[u8; 32]]) -> bool {
+    Verifier::<Sha256Hasher> { config: TreeConfig::default(), hasher: PhantomData }.verify_consistency(old_root, new_root, old_size, new_size, proof)
+}
+
+// root of the tree `MerkleTree::new` would build, computed level by level without any node
+pub fn compute_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    if leaves.is_empty() {
+        // same as the root of an empty tree
+        return EMPTY_ROOT;
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level.chunks(2).map(|pair| {
+            // if we have an odd number of nodes we duplicate the last one
+            let right = pair.get(1).unwrap_or(&pair[0]);
+            hash_pair::<Sha256Hasher, 32>(&pair[0], right)
+        }).collect();
+    }
+
+    level[0]
+}
+
+pub fn compute_combined_root(a: &[[u8; 32]], b: &[[u8; 32]]) -> [u8; 32] {
+    compute_root(&[a, b].concat())
+}
+
+// `[{"sibling":"<hex>","right":true}]`, right being true when the sibling hashes on the right
+pub fn proof_to_json(proofs: &[([u8; 32], u8)]) -> String {
+    let steps: Vec<String> = proofs.iter().map(|(sibling, direction)| {
+        format!("{{\"sibling\":\"{}\",\"right\":{}}}", hex::encode(sibling), *direction == 1)
+    }).collect();
+
+    format!("[{}]", steps.join(","))
+}
+
+pub fn proof_from_json(input: &str) -> Result<Proof, MerkleError> {
+    let objects = json::parse_object_array(input).ok_or(MerkleError::MalformedProof)?;
+
+    objects.iter().map(|object| {
+        let direction = match json::get(object, "right") {
+            Some(JsonValue::Bool(right)) => *right as u8,
+            _ => return Err(MerkleError::MalformedProof),
+        };
+        let Some(JsonValue::Str(data)) = json::get(object, "sibling") else {
+            return Err(MerkleError::MalformedProof);
+        };
+
+        let mut sibling = [0u8; 32];
+        hex::decode_to_slice(data, &mut sibling).map_err(|_| MerkleError::MalformedProof)?;
+
+        Ok((sibling, direction))
+    }).collect()
+}
+
+// `[{"position":"left"|"right","data":"0x..."}]` as merkletreejs lays out its proofs
+pub fn proof_to_merkletreejs_json(proofs: &[([u8; 32], u8)]) -> String {
+    let steps: Vec<String> = proofs.iter().map(|(sibling, direction)| {
+        let position = if *direction == 1 { "right" } else { "left" };
+        format!("{{\"position\":\"{}\",\"data\":\"0x{}\"}}", position, hex::encode(sibling))
+    }).collect();
+
+    format!("[{}]", steps.join(","))
+}
+
+pub fn proof_from_merkletreejs_json(input: &str) -> Result<Proof, MerkleError> {
+    let objects = json::parse_object_array(input).ok_or(MerkleError::MalformedProof)?;
+
+    objects.iter().map(|object| {
+        let direction = match json::get(object, "position") {
+            Some(JsonValue::Str(p)) if p == "right" => 1,
+            Some(JsonValue::Str(p)) if p == "left" => 0,
+            _ => return Err(MerkleError::MalformedProof),
+        };
+        let data = match json::get(object, "data") {
+            Some(JsonValue::Str(d)) => d.strip_prefix("0x").unwrap_or(d),
+            _ => return Err(MerkleError::MalformedProof),
+        };
+
+        let mut sibling = [0u8; 32];
+        hex::decode_to_slice(data, &mut sibling).map_err(|_| MerkleError::MalformedProof)?;
+
+        Ok((sibling, direction))
+    }).collect()
+}
+
+#[derive(Debug, Clone)]
+pub enum Node<const N: usize = 32> {
+    Empty,
+    Node { 
+        hash: [u8; N],
+        left: Arc<Node<N>>,
+        right: Arc<Node<N>>,
+    },
+    Leaf {
+        hash: [u8; N],
+    },
+    // a subtree we only know the hash of
+    Pruned {
+        hash: [u8; N],
+    },
+}
+
+// zeroes the hashes of the nodes `node` is the last owner of. A node shared with a clone or a
+// partial tree is still in use, it and everything under it are left to their other owner.
+#[cfg(feature = "zeroize")]
+fn wipe_nodes<const N: usize>(node: &mut Arc<Node<N>>) {
+    use zeroize::Zeroize;
+
+    let Some(node) = Arc::get_mut(node) else { return };
+    match node {
+        Node::Node { hash, left, right } => {
+            hash.zeroize();
+            wipe_nodes(left);
+            wipe_nodes(right);
+        }
+        Node::Leaf { hash } | Node::Pruned { hash } => hash.zeroize(),
+        Node::Empty => {},
+    }
+}
+
+// wiping happens on the trees' drop, `Node` itself stays a plain enum callers can take apart
+#[cfg(feature = "zeroize")]
+impl<H: MerkleHasher<N>, const N: usize> Drop for GenericMerkleTree<H, N> {
+    fn drop(&mut self) {
+        // the leaf list and the index share the leaves with the tree, let go of them first
+        self.leaf_index.clear();
+        self.leaves.clear();
+        wipe_nodes(&mut self.root);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize> Drop for PartialTree<N> {
+    fn drop(&mut self) {
+        wipe_nodes(&mut self.root);
+    }
+}
+
+impl<const N: usize> Node<N> {
+    // an Empty node stands for a missing right child, it has no hash of its own
+    pub fn hash(&self) -> Option<&[u8; N]> {
+        match self {
+            Node::Node { hash, ..} => Some(hash),
+            Node::Leaf { hash, ..} => Some(hash),
+            Node::Pruned { hash } => Some(hash),
+            Node::Empty => None,
+        }
+    }
+
+    // for nodes that can't be Empty, leaves, roots of non empty trees and left children
+    fn known_hash(&self) -> &[u8; N] {
+        self.hash().expect("Empty nodes don't have a hash")
+    }
+
+    pub fn get_left(&self) -> Option<&Self> {
+        match self {
+            Node::Node{ left, ..} => Some(left),
+            _ => None,
+        }
+    }
+
+    pub fn get_right(&self) -> Option<&Self> {
+        match self {
+            Node::Node{ right, ..} => { if let Node::Empty = right.as_ref() { None } else { Some(right) }},
+            _ => None,
+        }
+    }
+
+    // sibling of the child with `child_hash` and the direction byte a proof step would carry,
+    // a left child without a right one is its own sibling
+    pub fn sibling_of(&self, child_hash: &[u8; N]) -> Option<(&Node<N>, u8)> {
+        let left = self.get_left()?;
+        let right = self.get_right();
+
+        if left.hash() == Some(child_hash) {
+            Some((right.unwrap_or(left), 1))
+        } else if right.and_then(Node::hash) == Some(child_hash) {
+            Some((left, 0))
+        } else {
+            None
+        }
+    }
+
+    fn gen_proof(&self, height: usize, index: usize, odd_node: OddNodeStrategy) -> Proof<N> {
+        self.gen_proof_refs(height, index, odd_node).into_iter().map(|(hash, direction)| (*hash, direction)).collect()
+    }
+
+    // walks down from this node, the bits of the leaf index tell us which child to follow
+    fn gen_proof_refs(&self, height: usize, index: usize, odd_node: OddNodeStrategy) -> Vec<(&[u8; N], u8)> {
+        let mut proofs = Vec::with_capacity(height);
+
+        let mut n = self;
+        for h in (0..height).rev() {
+            let Some(left) = n.get_left() else { break };
+
+            if (index >> h) & 1 == 0 {
+                // sibling is right then
+                match (n.get_right(), odd_node) {
+                    (Some(right), _) => proofs.push((right.known_hash(), 1)),
+                    (None, OddNodeStrategy::Duplicate) => proofs.push((left.known_hash(), 1)),
+                    // a promoted node has nothing to be hashed with
+                    (None, OddNodeStrategy::Promote) => {},
+                }
+                n = left;
+            } else {
+                let right = n.get_right().unwrap(); // an odd index always has a right sibling
+                proofs.push((left.known_hash(), 0));
+                n = right;
+            }
+        }
+
+        // we went from the root down, proofs go from the leaf up
+        proofs.reverse();
+        proofs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac_sha256::Hash;
+    use sha3::{Digest, Keccak256};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::error::Error;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{
+        combine_proofs, commit_and_prove, construction_hash_count, verify_consistency, decode_proof, decode_proof_compact, encode_proof, encode_proof_compact, roots_equal, verify_proof, compute_combined_root, proof_byte_size, proof_subtree_side, Side, proof_from_json, proof_from_merkletreejs_json, proof_to_json, proof_to_merkletreejs_json,
+        GenericMerkleTree, MerkleError, MerkleHasher, MerkleProof, MerkleTree, MerkleTreeBuilder, MerkleTreeCache, Node, OddNodeStrategy, ProofVerifier, PROOF_STEP_SIZE, RangeProof, VerifyOutcome, VerifyResult,
+    };
+
+    #[test]
+    fn test_merkle_root() {
+        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
+        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
+
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let mtree = MerkleTree::new(hashes);
+
+        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+    }
+
+    #[test]
+    fn test_proofs_0() {
+        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
+        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in &contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let first = *hashes.first().unwrap();
+
+        let mtree = MerkleTree::new(hashes);
+
+        let proofs = mtree.generate_proofs(first).unwrap().into_tuples();
+        let expected_root = mtree.root_hash();
+
+        assert_eq!(expected_root.to_vec(), expected_hash);
+
+        let root = MerkleTree::verify(contents[0].as_bytes().to_vec(), proofs);
+
+        assert_eq!(&root, expected_root);
+    }
+
+
+    #[test]
+    fn test_proofs_1() {
+        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
+        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in &contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let second = hashes[1];
+
+        let mtree = MerkleTree::new(hashes);
+
+        let proofs = mtree.generate_proofs(second).unwrap().into_tuples();
+        let expected_root = mtree.root_hash();
+
+        assert_eq!(expected_root.to_vec(), expected_hash);
+
+        let root = MerkleTree::verify(contents[1].as_bytes().to_vec(), proofs);
+
+        assert_eq!(&root, expected_root);
+    }
+
+    #[test]
+    fn test_proofs_2() {
+        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
+        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in &contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let third = hashes[2];
+
+        let mtree = MerkleTree::new(hashes);
+
+        let proofs = mtree.generate_proofs(third).unwrap().into_tuples();
+        let expected_root = mtree.root_hash();
+
+        assert_eq!(expected_root.to_vec(), expected_hash);
+
+        let root = MerkleTree::verify(contents[2].as_bytes().to_vec(), proofs);
+
+        assert_eq!(&root, expected_root);
+    }
+
+    #[test]
+    fn test_proofs_3() {
+        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
+        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in &contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let last = *hashes.last().unwrap();
+
+        let mtree = MerkleTree::new(hashes);
+
+        let proofs = mtree.generate_proofs(last).unwrap().into_tuples();
+        let expected_root = mtree.root_hash();
+
+        assert_eq!(expected_root.to_vec(), expected_hash);
+
+        let root = MerkleTree::verify(contents[3].as_bytes().to_vec(), proofs);
+
+        assert_eq!(&root, expected_root);
+    }
+
+    #[test]
+    fn test_root_other_set() {
+        // Test from https://github.com/olivmath/merkly/blob/main/test/merkle_root/test_merkle_root.py#L99
+        let expected_hash = hex::decode("14ede5e8e97ad9372327728f5099b95604a39593cac3bd38a343ad76205213e7").unwrap();
+        let contents = vec!["a", "b", "c", "d"];
+
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let mtree = MerkleTree::new(hashes);
+
+        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+    }
+
+    #[test]
+    fn test_root_other_set_1() {
+        let expected_hash = hex::decode("e5a01fee14e0ed5c48714f22180f25ad8365b53f9779f79dc4a3d7e93963f94a").unwrap();
+        let contents = vec!["a", "b"];
+
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let mtree = MerkleTree::new(hashes);
+        
+        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+    }
+
+    #[test]
+    fn test_root_other_set_2() {
+        // the odd node is duplicated at every level, like for the 3 and 6 leaves vectors.
+        // d71f8983ad4ee170f8129f1ebcdd7440be7798d8e1c80420bf11f1eced610dba is the root you get when
+        // the odd node is promoted unhashed instead, which would break the other two vectors
+        let expected_hash = hex::decode("dd14d0ba516bb654a3052b76f051db026f4e322d0be081468fab99440f9e7305").unwrap();
+        let contents = ["a", "b", "c", "d", "e"];
+
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let mtree = MerkleTree::new(hashes.clone());
+
+        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+
+        // "e" gets paired with itself twice on its way up
+        for hash in hashes {
+            let proofs = mtree.generate_proofs(hash).unwrap().into_tuples();
+            assert!(MerkleTree::verify_zerocopy(&hash, &proofs, mtree.root_hash()));
+        }
+    }
+
+    #[test]
+    fn test_root_other_set_3() {
+        // test from https://github.com/merkletreejs/merkletreejs/blob/master/test/MerkleTree.test.js#L188
+        let expected_hash = hex::decode("44205acec5156114821f1f71d87c72e0de395633cd1589def6d4444cc79f8103").unwrap();
+        let contents = vec!["a", "b", "c", "d", "e", "f"];
+
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let mtree = MerkleTree::new(hashes);
+        
+        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+    }
+
+    #[test]
+    fn test_root_other_set_4() {
+        // test from https://github.com/merkletreejs/merkletreejs/blob/master/test/MerkleTree.test.js#L218
+        let expected_hash = hex::decode("d31a37ef6ac14a2db1470c4316beb5592e6afd4465022339adafda76a18ffabe").unwrap();
+        let contents = vec!["a", "b", "c"];
+
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let mtree = MerkleTree::new(hashes);
+        
+        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+    }
+
+    #[test]
+    fn test_reconstruct_from_proofs() {
+        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
+        let mut hashes: Vec<[u8; 32]> = vec![];
+        for data in &contents {
+            let hash = Hash::hash(data.as_bytes());
+            hashes.push(hash);
+        }
+
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs: Vec<_> = hashes.iter().map(|h| mtree.generate_proofs(*h).unwrap().into_tuples()).collect();
+
+        let rebuilt = MerkleTree::reconstruct_from_proofs(&hashes, &proofs).unwrap();
+        assert_eq!(rebuilt.root_hash(), mtree.root_hash());
+
+        // tampering with a shared sibling makes the proofs disagree
+        let mut tampered = proofs.clone();
+        tampered[0][1].0[0] ^= 1;
+        assert_eq!(MerkleTree::reconstruct_from_proofs(&hashes, &tampered).err(), Some(MerkleError::InconsistentProofs));
+    }
+
+    #[test]
+    fn test_affected_indices() {
+        let hashes: Vec<[u8; 32]> = (0..8u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes);
+
+        assert_eq!(mtree.affected_indices(0), vec![1, 2, 3, 4, 5, 6, 7]);
+
+        // the last leaf of a 5-leaf tree is paired with itself so its own proof changes too
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes);
+
+        assert_eq!(mtree.affected_indices(4), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_level_widths() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes);
+
+        assert_eq!(mtree.level_widths(), vec![5, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_verify_detailed() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proofs(hashes[1]).unwrap().into_tuples();
+        let root = mtree.root_hash();
+
+        assert_eq!(MerkleTree::verify_detailed(contents[1].as_bytes(), &proofs, root), VerifyOutcome::Valid);
+
+        let computed_root = MerkleTree::verify(contents[2].as_bytes().to_vec(), proofs.clone());
+        let first_mismatch = computed_root.iter().zip(root).position(|(a, b)| a != b).unwrap();
+        assert_eq!(MerkleTree::verify_detailed(contents[2].as_bytes(), &proofs, root), VerifyOutcome::Invalid { computed_root, first_mismatch });
+
+        let mut bad_direction = proofs.clone();
+        bad_direction[0].1 = 2;
+        assert!(matches!(MerkleTree::verify_detailed(contents[1].as_bytes(), &bad_direction, root), VerifyOutcome::Malformed { .. }));
+
+        let mut zero_sibling = proofs.clone();
+        zero_sibling[1].0 = [0u8; 32];
+        assert!(matches!(MerkleTree::verify_detailed(contents[1].as_bytes(), &zero_sibling, root), VerifyOutcome::Malformed { .. }));
+    }
+
+    #[test]
+    fn test_concurrent_proofs() {
+        let contents: Vec<[u8; 8]> = (0..64u64).map(|i| i.to_le_bytes()).collect();
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data)).collect();
+        let mtree = Arc::new(MerkleTree::new(hashes));
+
+        let handles: Vec<_> = (0..16).map(|t| {
+            let mtree = Arc::clone(&mtree);
+            let contents = contents.clone();
+            thread::spawn(move || {
+                for i in 0..1000 {
+                    let index = (t * 1000 + i) % contents.len();
+                    let proofs = mtree.generate_proof_by_index(index).unwrap();
+                    assert_eq!(&MerkleTree::verify(contents[index].to_vec(), proofs), mtree.root_hash());
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_compute_combined_root() {
+        let a: Vec<[u8; 32]> = ["a", "b", "c"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let b: Vec<[u8; 32]> = ["d", "e"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let mtree = MerkleTree::new([a.clone(), b.clone()].concat());
+
+        assert_eq!(&compute_combined_root(&a, &b), mtree.root_hash());
+    }
+
+    #[test]
+    fn test_merkletreejs_json() {
+        let contents = ["a", "b", "c"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proofs(hashes[2]).unwrap().into_tuples();
+
+        let json = proof_to_merkletreejs_json(&proofs);
+        assert!(json.starts_with(&format!("[{{\"position\":\"right\",\"data\":\"0x{}\"}}", hex::encode(hashes[2]))));
+
+        let imported = proof_from_merkletreejs_json(&json).unwrap();
+        assert_eq!(imported, proofs);
+        assert_eq!(&MerkleTree::verify(contents[2].as_bytes().to_vec(), imported), mtree.root_hash());
+
+        assert!(proof_from_merkletreejs_json("[{\"position\":\"up\",\"data\":\"0x00\"}]").is_err());
+    }
+
+    #[test]
+    fn test_last_leaf_duplication_levels() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        assert_eq!(MerkleTree::new(hashes).last_leaf_duplication_levels(), 2);
+
+        let hashes: Vec<[u8; 32]> = (0..7u8).map(|i| Hash::hash(&[i])).collect();
+        assert_eq!(MerkleTree::new(hashes).last_leaf_duplication_levels(), 1);
+    }
+
+    #[test]
+    fn test_verify_root_hex() {
+        let root_hex = "5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc";
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proofs(hashes[3]).unwrap().into_tuples();
+
+        assert_eq!(MerkleTree::verify_root_hex(contents[3].as_bytes(), &proofs, root_hex), Ok(true));
+        assert_eq!(MerkleTree::verify_root_hex(contents[0].as_bytes(), &proofs, root_hex), Ok(false));
+        assert_eq!(MerkleTree::verify_root_hex(contents[3].as_bytes(), &proofs, "5f30cc"), Err(MerkleError::InvalidHex));
+    }
+
+    #[test]
+    fn test_original_index() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let mtree = MerkleTree::new_with_sorted_leaves(hashes.clone());
+
+        let mut sorted = hashes.clone();
+        sorted.sort();
+        assert_eq!(mtree.root_hash(), MerkleTree::new(sorted.clone()).root_hash());
+
+        for (i, hash) in sorted.iter().enumerate() {
+            assert_eq!(&hashes[mtree.original_index(i).unwrap()], hash);
+        }
+        assert_eq!(mtree.original_index(4), Err(MerkleError::IndexOutOfBounds));
+    }
+
+    #[test]
+    fn test_verify_sized() {
+        let contents = ["a", "b", "c"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proofs(hashes[0]).unwrap().into_tuples();
+        let sized_root = mtree.sized_root();
+
+        assert_ne!(&sized_root, mtree.root_hash());
+        assert!(MerkleTree::verify_sized(contents[0].as_bytes(), &proofs, &sized_root, 3));
+        // a 4-leaf tree duplicating "c" has the same root but not the same size
+        assert!(!MerkleTree::verify_sized(contents[0].as_bytes(), &proofs, &sized_root, 4));
+    }
+
+    #[test]
+    fn test_prune_to() {
+        let contents: Vec<[u8; 1]> = (0..8u8).map(|i| [i]).collect();
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data)).collect();
+        let mtree = MerkleTree::new(hashes);
+
+        let partial = mtree.prune_to(&[1, 6]);
+        assert_eq!(partial.root_hash(), mtree.root_hash());
+
+        for i in [1, 6] {
+            let proofs = partial.generate_proof(i).unwrap();
+            assert_eq!(proofs, mtree.generate_proof_by_index(i).unwrap());
+            assert_eq!(&MerkleTree::verify(contents[i].to_vec(), proofs), mtree.root_hash());
+        }
+
+        assert_eq!(partial.generate_proof(2), Err(MerkleError::LeafNotFound));
+    }
+
+    #[test]
+    fn test_verify_any_index() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        for (i, hash) in hashes.iter().enumerate() {
+            let proofs = mtree.generate_proof_by_index(i).unwrap();
+            assert_eq!(MerkleTree::verify_any_index(*hash, &proofs, mtree.root_hash()), Some(i));
+        }
+
+        let proofs = mtree.generate_proof_by_index(0).unwrap();
+        assert_eq!(MerkleTree::verify_any_index(hashes[1], &proofs, mtree.root_hash()), None);
+    }
+
+    #[test]
+    fn test_combine_proofs() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let proofs: Vec<_> = [0, 1].iter().map(|&i| (i, mtree.generate_proof_by_index(i).unwrap())).collect();
+        let multiproof = combine_proofs(&proofs, 4);
+
+        // the leaves are each other's sibling and share the one above their common parent
+        assert_eq!(multiproof.nodes.len(), 1);
+        assert!(multiproof.verify(&hashes[0..2], mtree.root_hash()));
+        assert!(!multiproof.verify(&[hashes[1], hashes[0]], mtree.root_hash()));
+    }
+
+    #[test]
+    fn test_algorithm() {
+        assert_eq!(MerkleTree::algorithm(), "sha256");
+    }
+
+    #[test]
+    fn test_verify_with_width() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let root = mtree.root_hash();
+
+        for (i, hash) in hashes.iter().enumerate().take(4) {
+            let siblings: Vec<[u8; 32]> = mtree.generate_proof_by_index(i).unwrap().iter().map(|(s, _)| *s).collect();
+            assert!(MerkleTree::verify_with_width(*hash, &siblings, i, 5, root));
+            assert!(!MerkleTree::verify_with_width(*hash, &siblings, i ^ 1, 5, root));
+        }
+
+        // the last leaf is paired with itself twice, only the top sibling is left
+        let proofs = mtree.generate_proof_by_index(4).unwrap();
+        assert!(MerkleTree::verify_with_width(hashes[4], &[proofs[2].0], 4, 5, root));
+        assert!(!MerkleTree::verify_with_width(hashes[4], &[proofs[2].0], 4, 6, root));
+    }
+
+    #[test]
+    fn test_iter_internal() {
+        let hashes: Vec<[u8; 32]> = ["a", "b", "c", "d"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let internal: Vec<_> = mtree.iter_internal().collect();
+        assert_eq!(internal.len(), 3);
+
+        let ab = Hash::hash(&[hashes[0], hashes[1]].concat());
+        let cd = Hash::hash(&[hashes[2], hashes[3]].concat());
+        assert_eq!(internal[0], (*mtree.root_hash(), ab, Some(cd)));
+        assert_eq!(internal[1], (ab, hashes[0], Some(hashes[1])));
+        assert_eq!(internal[2], (cd, hashes[2], Some(hashes[3])));
+
+        let mtree = MerkleTree::new(hashes[..3].to_vec());
+        assert!(mtree.iter_internal().any(|(_, left, right)| left == hashes[2] && right.is_none()));
+    }
+
+    #[test]
+    fn test_commit_and_prove() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let (root, proofs) = commit_and_prove(hashes.clone(), 2).unwrap();
+        assert_eq!(MerkleTree::verify(contents[2].as_bytes().to_vec(), proofs), root);
+
+        assert_eq!(commit_and_prove(hashes, 4), Err(MerkleError::IndexOutOfBounds));
+        assert_eq!(commit_and_prove(vec![], 0), Err(MerkleError::EmptyTree));
+    }
+
+    #[test]
+    fn test_truncated_hashes() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let full = MerkleTree::new(hashes.clone());
+        let mtree = MerkleTree::new_truncated(hashes.clone(), 16).unwrap();
+
+        assert_ne!(mtree.root_hash(), full.root_hash());
+        assert_eq!(mtree.root_hash()[16..], [0u8; 16]);
+
+        let proofs = mtree.generate_proofs(hashes[0]).unwrap().into_tuples();
+        assert_eq!(&MerkleTree::verify_truncated(contents[0].as_bytes().to_vec(), proofs.clone(), mtree.truncation()), mtree.root_hash());
+        assert_ne!(&MerkleTree::verify(contents[0].as_bytes().to_vec(), proofs), mtree.root_hash());
+
+        assert!(matches!(MerkleTree::new_truncated(hashes, 33), Err(MerkleError::InvalidTruncation)));
+    }
+
+    #[test]
+    fn test_proof_subtree_side() {
+        let hashes: Vec<[u8; 32]> = ["a", "b", "c", "d"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes);
+
+        let sides: Vec<_> = (0..4).map(|i| proof_subtree_side(&mtree.generate_proof_by_index(i).unwrap())).collect();
+        assert_eq!(sides, vec![Some(Side::Left), Some(Side::Left), Some(Side::Right), Some(Side::Right)]);
+
+        assert_eq!(proof_subtree_side(&[]), None);
+    }
+
+    #[test]
+    fn test_verify_in_header() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proofs(hashes[1]).unwrap().into_tuples();
+
+        // version, previous block hash, merkle root, timestamp
+        let header = [&1u32.to_le_bytes()[..], &[0xaa; 32], mtree.root_hash(), &1700000000u32.to_le_bytes()].concat();
+
+        assert!(MerkleTree::verify_in_header(contents[1].as_bytes(), &proofs, &header, 36));
+        assert!(!MerkleTree::verify_in_header(contents[1].as_bytes(), &proofs, &header, 4));
+        assert!(!MerkleTree::verify_in_header(contents[1].as_bytes(), &proofs, &header, 50));
+    }
+
+    #[test]
+    fn test_multiproof_size() {
+        let hashes: Vec<[u8; 32]> = (0..8u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let proofs: Vec<_> = [0, 1, 2].iter().map(|&i| (i, mtree.generate_proof_by_index(i).unwrap())).collect();
+        let multiproof = combine_proofs(&proofs, 8);
+
+        let singles: usize = proofs.iter().map(|(_, p)| p.len()).sum();
+        assert_eq!(singles, 9);
+        // leaf 3 and the right half of the tree are all that can't be recomputed
+        assert_eq!(multiproof.size(), 2);
+        assert!(multiproof.verify(&hashes[0..3], mtree.root_hash()));
+
+        // the last of 5 leaves is paired with itself twice, only the left half is carried
+        let mtree = MerkleTree::new(hashes[..5].to_vec());
+        let multiproof = mtree.generate_multiproof(&hashes[4..5]).unwrap();
+        assert_eq!(multiproof.size(), 1);
+        assert!(multiproof.verify(&hashes[4..5], mtree.root_hash()));
+
+        let range = mtree.range_proof(3, 5).unwrap();
+        assert_eq!(range.size(), 2);
+        assert!(range.verify(&hashes[3..5], mtree.root_hash()));
+        assert!(!RangeProof { leaf_count: 6, ..range }.verify(&hashes[3..5], mtree.root_hash()));
+    }
+
+    #[test]
+    fn test_verify_detailed_mismatch_index() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proofs(hashes[0]).unwrap().into_tuples();
+
+        let mut root = *mtree.root_hash();
+        root[17] ^= 0x01;
+
+        assert_eq!(
+            MerkleTree::verify_detailed(contents[0].as_bytes(), &proofs, &root),
+            VerifyOutcome::Invalid { computed_root: *mtree.root_hash(), first_mismatch: 17 },
+        );
+    }
+
+    #[test]
+    fn test_sorted_view() {
+        let hashes: Vec<[u8; 32]> = ["a", "b", "c", "d", "a"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let view = mtree.sorted_view();
+        assert!(view.windows(2).all(|w| w[0].1 <= w[1].1));
+        for (index, hash) in &view {
+            assert_eq!(&hashes[*index], hash);
+        }
+
+        // duplicates keep their tree order
+        let dups: Vec<usize> = view.iter().filter(|(_, h)| h == &hashes[0]).map(|(i, _)| *i).collect();
+        assert_eq!(dups, vec![0, 4]);
+        assert!(view.binary_search_by_key(&Hash::hash(b"e"), |(_, h)| *h).is_err());
+    }
+
+    #[test]
+    fn test_verify_build() {
+        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
+        let claimed_root: [u8; 32] = expected_hash.try_into().unwrap();
+        let hashes: Vec<[u8; 32]> = ["Hello", "Hi", "Hey", "Hola"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let mtree = MerkleTree::verify_build(hashes.clone(), &claimed_root).unwrap();
+        assert_eq!(mtree.root_hash(), &claimed_root);
+
+        let mut swapped = hashes;
+        swapped.swap(0, 1);
+        assert!(matches!(MerkleTree::verify_build(swapped, &claimed_root), Err(MerkleError::RootMismatch)));
+    }
+
+    #[test]
+    fn test_proof_byte_size() {
+        let hashes: Vec<[u8; 32]> = (0..16u8).map(|i| Hash::hash(&[i])).collect();
+        let proofs = MerkleTree::new(hashes).generate_proof_by_index(5).unwrap();
+
+        assert_eq!(proofs.len(), 4);
+        assert_eq!(proof_byte_size(&proofs), 132);
+    }
+
+    // counts allocations per thread so tests running in parallel don't interfere
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|a| a.set(a.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn test_verify_zerocopy() {
+        let hashes: Vec<[u8; 32]> = (0..8u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proof_by_index(5).unwrap();
+        let root = *mtree.root_hash();
+
+        let before = ALLOCATIONS.with(|a| a.get());
+        let valid = MerkleTree::verify_zerocopy(&hashes[5], &proofs, &root);
+        let invalid = MerkleTree::verify_zerocopy(&hashes[4], &proofs, &root);
+        let after = ALLOCATIONS.with(|a| a.get());
+
+        assert!(valid);
+        assert!(!invalid);
+        assert_eq!(after - before, 0);
+
+        // the allocating verify does show up in the count
+        let root_from_data = MerkleTree::verify([5u8].to_vec(), proofs);
+        assert_eq!(root_from_data, root);
+        assert!(ALLOCATIONS.with(|a| a.get()) > after);
+    }
+
+    #[test]
+    fn test_proofs_leaf_not_found() {
+        let hashes: Vec<[u8; 32]> = ["Hello", "Hi", "Hey", "Hola"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes);
+
+        assert!(mtree.generate_proofs(Hash::hash(b"Bonjour")).is_err());
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let hashes: Vec<[u8; 32]> = ["Hello", "Hi", "Hey", "Hola"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes);
+
+        assert!(matches!(mtree.generate_proofs(Hash::hash(b"Bonjour")), Err(MerkleError::LeafNotFound)));
+
+        let boxed = || -> Result<MerkleProof, Box<dyn Error>> { Ok(mtree.generate_proofs(Hash::hash(b"Bonjour"))?) };
+        let err = boxed().unwrap_err();
+        assert_eq!(err.downcast_ref::<MerkleError>(), Some(&MerkleError::LeafNotFound));
+        assert_eq!(err.to_string(), "leaf not found in the tree");
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        assert!(matches!(MerkleTree::try_new(vec![]), Err(MerkleError::EmptyTree)));
+
+        // the infallible constructor gives an empty tree rather than panicking
+        let mtree = MerkleTree::new(vec![]);
+        assert_eq!(mtree.root_hash(), &[0u8; 32]);
+        assert_eq!(mtree.generate_proofs(Hash::hash(b"a")), Err(MerkleError::LeafNotFound));
+        assert_eq!(mtree.generate_proof_by_index(0), Err(MerkleError::IndexOutOfBounds));
+        assert!(mtree.level_widths().is_empty());
+    }
+
+    #[test]
+    fn test_verify_against_root() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proofs(hashes[2]).unwrap().into_tuples();
+
+        assert!(MerkleTree::verify_against_root(contents[2].as_bytes().to_vec(), proofs.clone(), mtree.root_hash()));
+
+        let mut tampered = proofs;
+        tampered[1].0[31] ^= 0x80;
+        assert!(!MerkleTree::verify_against_root(contents[2].as_bytes().to_vec(), tampered, mtree.root_hash()));
+    }
+
+    struct DoubleSha256Hasher;
+
+    impl MerkleHasher for DoubleSha256Hasher {
+        const ALGORITHM_ID: &'static str = "sha256d";
+
+        fn hash(data: &[u8]) -> [u8; 32] {
+            Hash::hash(&Hash::hash(data))
+        }
+    }
+
+    // what Solidity's keccak256 computes
+    struct Keccak256Hasher;
+
+    impl MerkleHasher for Keccak256Hasher {
+        const ALGORITHM_ID: &'static str = "keccak256";
+
+        fn hash(data: &[u8]) -> [u8; 32] {
+            Keccak256::digest(data).into()
+        }
+    }
+
+    // leaves and proof of leaf 0 from OpenZeppelin's `StandardMerkleTree.of(values, ["address", "uint256"])`
+    // over the first five hardhat accounts, the same vector alloy-merkle-tree checks against
+    const OZ_ACCOUNTS: [(&str, u64); 5] = [
+        ("f39fd6e51aad88f6f4ce6ab8827279cfffb92266", 10000),
+        ("70997970c51812dc3a010c7d01b50e0d17dc79c8", 1000),
+        ("3c44cdddb6a900fa2b585dd299e03d12fa4293bc", 100),
+        ("90f79bf6eb2c4f870365e785982e1f101e93b906", 10),
+        ("15d34aaf54267db7d7c367839aaf71a00a2c6a65", 1),
+    ];
+    const OZ_ROOT: &str = "2b4b963c699c531f94ca8f8a0ef76c5d28f067d79927c035a44296190c2d8029";
+    const OZ_PROOF: [&str; 3] = [
+        "8ee56d16226ff6684927054c33cd505c4eee1ebabbffe198460d00cb083aaebd",
+        "fa31eb8d65ff2307b7026df667a06a19aade0151ed701ed2307295ae4fa48364",
+        "f0768f444c5a27a6bb7c9203b0b5b147e501ff7b7784e0363e5751590962b034",
+    ];
+
+    fn unhex32(s: &str) -> [u8; 32] {
+        hex::decode(s).unwrap().try_into().unwrap()
+    }
+
+    // keccak256(keccak256(abi.encode(account, amount))), the standard tree's leaf hash
+    fn oz_leaves() -> Vec<[u8; 32]> {
+        OZ_ACCOUNTS.iter().map(|(account, amount)| {
+            let mut encoded = [0u8; 64];
+            encoded[12..32].copy_from_slice(&hex::decode(account).unwrap());
+            encoded[56..].copy_from_slice(&amount.to_be_bytes());
+            Keccak256Hasher::hash(&Keccak256Hasher::hash(&encoded))
+        }).collect()
+    }
+
+    // stands in for a 20 byte hash like RIPEMD160
+    struct Sha256_160Hasher;
+
+    impl MerkleHasher<20> for Sha256_160Hasher {
+        const ALGORITHM_ID: &'static str = "sha256-160";
+
+        fn hash(data: &[u8]) -> [u8; 20] {
+            Hash::hash(data)[..20].try_into().unwrap()
+        }
+    }
+
+    struct Sha512Hasher;
+
+    impl MerkleHasher<64> for Sha512Hasher {
+        const ALGORITHM_ID: &'static str = "sha512";
+
+        fn hash(data: &[u8]) -> [u8; 64] {
+            sha2::Sha512::digest(data).into()
+        }
+    }
+
+    #[test]
+    fn test_custom_hasher() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| DoubleSha256Hasher::hash(data.as_bytes())).collect();
+
+        let mtree = GenericMerkleTree::<DoubleSha256Hasher>::new(hashes.clone());
+        assert_ne!(mtree.root_hash(), MerkleTree::new(hashes.clone()).root_hash());
+        assert_eq!(GenericMerkleTree::<DoubleSha256Hasher>::algorithm(), "sha256d");
+
+        let proofs = mtree.generate_proofs(hashes[1]).unwrap().into_tuples();
+        let root = GenericMerkleTree::<DoubleSha256Hasher>::verify(contents[1].as_bytes().to_vec(), proofs);
+        assert_eq!(&root, mtree.root_hash());
+    }
+
+    #[test]
+    fn test_domain_separation() {
+        let contents = ["a", "b", "c", "d"];
+
+        // without domain separation the concatenated children of a node pass as leaf data
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let cd = Hash::hash(&[hashes[2], hashes[3]].concat());
+        let forged = [hashes[0], hashes[1]].concat();
+        assert_eq!(&MerkleTree::verify(forged, vec![(cd, 1)]), mtree.root_hash());
+
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| MerkleTree::domain_separated_leaf(data.as_bytes())).collect();
+        let mtree = MerkleTree::new_with_domain_separation(hashes.clone());
+        assert!(mtree.domain_separation());
+
+        let proofs = mtree.generate_proofs(hashes[1]).unwrap().into_tuples();
+        assert_eq!(&MerkleTree::verify_with_domain_separation(contents[1].as_bytes().to_vec(), proofs.clone()), mtree.root_hash());
+
+        // the same forgery doesn't work anymore
+        let cd = proofs[1].0;
+        let forged = [hashes[0], hashes[1]].concat();
+        assert_ne!(&MerkleTree::verify_with_domain_separation(forged, vec![(cd, 1)]), mtree.root_hash());
+    }
+
+    #[test]
+    fn test_depth() {
+        for (count, depth) in [(1, 0), (2, 1), (4, 2), (5, 3), (8, 3), (9, 4)] {
+            let hashes: Vec<[u8; 32]> = (0..count as u8).map(|i| Hash::hash(&[i])).collect();
+            let mtree = MerkleTree::new(hashes);
+
+            assert_eq!(mtree.leaf_count(), count);
+            assert_eq!(mtree.depth(), depth);
+        }
+    }
+
+    #[test]
+    fn test_update_leaf() {
+        let mut hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mut mtree = MerkleTree::new(hashes.clone());
+
+        for (index, value) in [(1, 10u8), (4, 11u8)] {
+            let new_hash = Hash::hash(&[value]);
+            mtree.update_leaf(hashes[index], new_hash).unwrap();
+            hashes[index] = new_hash;
+
+            let expected = MerkleTree::new(hashes.clone());
+            assert_eq!(mtree.root_hash(), expected.root_hash());
+
+            let proofs = mtree.generate_proofs(new_hash).unwrap();
+            assert_eq!(proofs, expected.generate_proofs(new_hash).unwrap());
+        }
+
+        assert_eq!(mtree.update_leaf(Hash::hash(&[42]), [0u8; 32]), Err(MerkleError::LeafNotFound));
+    }
+
+    #[test]
+    fn test_from_data() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        let mtree = MerkleTree::from_data(contents.to_vec()).unwrap();
+        assert_eq!(mtree.root_hash(), MerkleTree::new(hashes).root_hash());
+
+        let raw: Vec<Vec<u8>> = contents.iter().map(|data| data.as_bytes().to_vec()).collect();
+        assert_eq!(MerkleTree::from_data(raw).unwrap().root_hash(), mtree.root_hash());
+
+        assert_eq!(MerkleTree::from_data(Vec::<&str>::new()).err(), Some(MerkleError::EmptyTree));
+    }
+
+    #[test]
+    fn test_encode_proof() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes);
+
+        for index in 0..5 {
+            let proofs = mtree.generate_proof_by_index(index).unwrap();
+            let bytes = encode_proof(&proofs);
+
+            assert_eq!(bytes.len(), proof_byte_size(&proofs));
+            assert_eq!(decode_proof(&bytes), Ok(proofs));
+        }
+
+        let mut bytes = encode_proof(&mtree.generate_proof_by_index(0).unwrap());
+        assert_eq!(decode_proof(&bytes[..bytes.len() - 1]), Err(MerkleError::MalformedProof));
+        bytes[32] = 2;
+        assert_eq!(decode_proof(&bytes), Err(MerkleError::MalformedProof));
+        assert_eq!(decode_proof(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let proof = mtree.generate_proofs(hashes[4]).unwrap();
+        assert_eq!(proof.leaf_hash, hashes[4]);
+        assert!(proof.verify(mtree.root_hash()));
+        assert!(!proof.verify(&hashes[0]));
+
+        // "e" is a left node with a duplicated sibling on the first two levels
+        let sides: Vec<bool> = proof.steps.iter().map(|step| step.is_right_sibling).collect();
+        assert_eq!(sides, [true, true, false]);
+
+        let mut forged = proof.clone();
+        forged.leaf_hash = hashes[3];
+        assert!(!forged.verify(mtree.root_hash()));
+
+        #[allow(deprecated)]
+        let tuples = mtree.generate_proof_tuples(hashes[4]).unwrap();
+        assert_eq!(MerkleProof::from_tuples(hashes[4], &tuples), proof);
+        assert_eq!(proof.into_tuples(), tuples);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+
+        let promote = MerkleTreeBuilder::new().odd_node(OddNodeStrategy::Promote).build(hashes.clone()).unwrap();
+        for mtree in [MerkleTree::new(hashes.clone()), MerkleTree::new_truncated(hashes.clone(), 16).unwrap(), MerkleTree::new_with_sorted_leaves(hashes.clone()), promote] {
+            let json = serde_json::to_string(&mtree).unwrap();
+            let loaded: MerkleTree = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(loaded.root_hash(), mtree.root_hash());
+            assert_eq!(loaded.truncation(), mtree.truncation());
+            assert_eq!(loaded.odd_node(), mtree.odd_node());
+            assert_eq!(loaded.original_index(0), mtree.original_index(0));
+            assert_eq!(loaded.generate_proofs(hashes[3]).unwrap(), mtree.generate_proofs(hashes[3]).unwrap());
+        }
+
+        let json = serde_json::to_string(&MerkleTree::new(hashes.clone())).unwrap();
+        assert!(serde_json::from_str::<GenericMerkleTree<DoubleSha256Hasher>>(&json).is_err());
+
+        // the original order has to be a permutation of the leaf positions
+        let leaves: Vec<String> = hashes[..2].iter().map(hex::encode).collect();
+        for order in ["[7,0]", "[0,0]", "[0]", "[1,0]"] {
+            let json = format!(r#"{{"algorithm":"sha256","truncate":32,"domain_separation":false,"leaves":{:?},"original_order":{}}}"#, leaves, order);
+            assert_eq!(serde_json::from_str::<MerkleTree>(&json).is_ok(), order == "[1,0]");
+        }
+    }
+
+    #[test]
+    fn test_generate_multiproof() {
+        let hashes: Vec<[u8; 32]> = (0..8u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let multiproof = mtree.generate_multiproof(&[hashes[6], hashes[1], hashes[4]]).unwrap();
+        assert_eq!(multiproof.indices, [1, 4, 6]);
+
+        let singles: usize = [1, 4, 6].iter().map(|&i| mtree.generate_proof_by_index(i).unwrap().len()).sum();
+        assert!(multiproof.size() < singles);
+
+        assert!(multiproof.verify(&[hashes[1], hashes[4], hashes[6]], mtree.root_hash()));
+        assert!(!multiproof.verify(&[hashes[1], hashes[4], hashes[7]], mtree.root_hash()));
+
+        assert_eq!(mtree.generate_multiproof(&[hashes[0], Hash::hash(b"Bonjour")]), Err(MerkleError::LeafNotFound));
+    }
+
+    #[test]
+    fn test_verify_with_max_depth() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let mut proofs = mtree.generate_proof_by_index(1).unwrap();
+        assert_eq!(MerkleTree::verify_with_max_depth(contents[1].as_bytes().to_vec(), proofs.clone(), mtree.depth()), Ok(*mtree.root_hash()));
+
+        proofs.extend(std::iter::repeat_n(([0u8; 32], 1), 1000));
+        assert_eq!(MerkleTree::verify_with_max_depth(contents[1].as_bytes().to_vec(), proofs, mtree.depth()), Err(MerkleError::ProofTooLong));
+    }
+
+    #[test]
+    fn test_leaf_index() {
+        let contents = ["Hello", "Hi", "Hey", "Hi"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        assert!(mtree.contains(&hashes[2]));
+        assert_eq!(mtree.leaf_index(&hashes[2]), Some(2));
+        assert_eq!(mtree.leaf_index(&hashes[3]), Some(1));
+
+        let absent = Hash::hash(b"Hola");
+        assert!(!mtree.contains(&absent));
+        assert_eq!(mtree.leaf_index(&absent), None);
+    }
+
+    #[test]
+    fn test_leaf_hashes() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+
+        assert_eq!(MerkleTree::new(hashes.clone()).leaf_hashes(), hashes);
+        assert_eq!(MerkleTree::new_with_sorted_leaves(hashes.clone()).leaf_hashes(), hashes);
+    }
+
+    #[test]
+    fn test_proofs_self_paired_leaf() {
+        for count in [3u8, 7] {
+            let hashes: Vec<[u8; 32]> = (0..count).map(|i| Hash::hash(&[i])).collect();
+            let mtree = MerkleTree::new(hashes.clone());
+
+            // the last leaf is the left of a pair with itself, its sibling is its own hash
+            let index = count as usize - 1;
+            let proofs = mtree.generate_proof_by_index(index).unwrap();
+            assert_eq!(proofs[0], (hashes[index], 1));
+            assert!(MerkleTree::verify_zerocopy(&hashes[index], &proofs, mtree.root_hash()));
+        }
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+
+        assert_send::<MerkleTree>();
+        assert_sync::<MerkleTree>();
+        assert_send::<GenericMerkleTree<DoubleSha256Hasher>>();
+        assert_sync::<GenericMerkleTree<DoubleSha256Hasher>>();
+    }
+
+    #[test]
+    fn test_push_leaf() {
+        let hashes: Vec<[u8; 32]> = (0..9u8).map(|i| Hash::hash(&[i])).collect();
+
+        let mut mtree = MerkleTree::new(vec![]);
+        for (count, hash) in hashes.iter().enumerate() {
+            mtree.push_leaf(*hash);
+
+            let expected = MerkleTree::new(hashes[..=count].to_vec());
+            assert_eq!(mtree.root_hash(), expected.root_hash());
+            assert_eq!(mtree.depth(), expected.depth());
+            assert_eq!(mtree.leaf_hashes(), expected.leaf_hashes());
+        }
+
+        let mut mtree = MerkleTree::new(hashes[..2].to_vec());
+        mtree.push_leaf(hashes[2]);
+        mtree.push_leaf(hashes[3]);
+        assert_eq!(mtree.root_hash(), MerkleTree::new(hashes[..4].to_vec()).root_hash());
+        assert!(MerkleTree::verify_zerocopy(&hashes[2], &mtree.generate_proof_by_index(2).unwrap(), mtree.root_hash()));
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes);
+        let root = *mtree.root_hash();
+
+        let proofs = mtree.generate_proof_by_index(2).unwrap();
+        drop(mtree);
+
+        assert!(verify_proof(contents[2].as_bytes(), &proofs, &root));
+        assert!(!verify_proof(contents[3].as_bytes(), &proofs, &root));
+    }
+
+    #[test]
+    fn test_verify_from_leaf_hash() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let proofs = mtree.generate_proof_by_index(3).unwrap();
+        let root = MerkleTree::verify_from_leaf_hash(hashes[3], proofs.clone());
+
+        assert_eq!(&root, mtree.root_hash());
+        assert_eq!(root, MerkleTree::verify(contents[3].as_bytes().to_vec(), proofs));
+    }
+
+    #[test]
+    fn test_new_checked() {
+        let hashes: Vec<[u8; 32]> = ["a", "a", "b"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+
+        assert_eq!(MerkleTree::new_checked(hashes.clone()).err(), Some(MerkleError::DuplicateLeaf(1)));
+        assert_eq!(MerkleTree::new(hashes.clone()).leaf_count(), 3);
+
+        let mtree = MerkleTree::new_checked(hashes[1..].to_vec()).unwrap();
+        assert_eq!(mtree.root_hash(), MerkleTree::new(hashes[1..].to_vec()).root_hash());
+    }
+
+    #[test]
+    fn test_sorted_pairs() {
+        // regression values, sha256 over sha256("a".."d")
+        let expected_root = hex::decode("4c6aae040ffada3d02598207b8485fcbe161c03f4cb3f660e4d341e7496ff3b2").unwrap();
+        let expected_proof = [
+            "18ac3e7343f016890c510e93f935261169d9e3f565436429830faf0934f4f8e4",
+            "18d79cb747ea174c59f3a3b41768672526d56fecc58360a99d283d0f9b0a3cc0",
+        ];
+
+        let hashes: Vec<[u8; 32]> = ["a", "b", "c", "d"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new_sorted(hashes.clone());
+        assert_eq!(mtree.root_hash().to_vec(), expected_root);
+
+        let siblings: Vec<[u8; 32]> = mtree.generate_proof_by_index(2).unwrap().iter().map(|(sibling, _)| *sibling).collect();
+        assert_eq!(siblings.iter().map(hex::encode).collect::<Vec<String>>(), expected_proof);
+        assert!(MerkleTree::verify_sorted(hashes[2], &siblings, mtree.root_hash()));
+        assert!(!MerkleTree::verify_sorted(hashes[1], &siblings, mtree.root_hash()));
+
+        // an odd node is still paired with itself
+        let mtree = MerkleTree::new_sorted(hashes[..3].to_vec());
+        assert_eq!(hex::encode(mtree.root_hash()), "b1da020d217b348265d6578cdfe4cc717bb79b5deaffce7fc167180e9e1ec8c6");
+        let ab = *MerkleTree::new_sorted(hashes[..2].to_vec()).root_hash();
+        assert!(MerkleTree::verify_sorted(hashes[2], &[hashes[2], ab], mtree.root_hash()));
+
+        // over keccak256 a pair of leaves hashes to the node OpenZeppelin's tree has for them,
+        // whichever way round they come, and its proofs fold to its root
+        let leaves = oz_leaves();
+        let pair = GenericMerkleTree::<Keccak256Hasher>::new_sorted(vec![leaves[4], leaves[1]]);
+        assert_eq!(hex::encode(pair.root_hash()), OZ_PROOF[2]);
+        let proof: Vec<[u8; 32]> = OZ_PROOF.iter().map(|sibling| unhex32(sibling)).collect();
+        assert!(GenericMerkleTree::<Keccak256Hasher>::verify_sorted(leaves[0], &proof, &unhex32(OZ_ROOT)));
+    }
+
+    #[test]
+    fn test_to_pretty_string() {
+        let hashes: Vec<[u8; 32]> = (0..4u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let pretty = mtree.to_pretty_string();
+        let lines: Vec<&str> = pretty.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], format!("node {}", &hex::encode(mtree.root_hash())[..8]));
+        assert_eq!(lines[2], format!("    leaf {}", &hex::encode(hashes[0])[..8]));
+
+        // the duplicated odd leaf shows up as an empty right child
+        let pretty = MerkleTree::new(hashes[..3].to_vec()).to_pretty_string();
+        assert_eq!(pretty.lines().last(), Some("    empty"));
+    }
+
+    #[test]
+    fn test_proof_len() {
+        for count in [4u8, 5] {
+            let hashes: Vec<[u8; 32]> = (0..count).map(|i| Hash::hash(&[i])).collect();
+            let mtree = MerkleTree::new(hashes.clone());
+
+            for hash in &hashes {
+                assert_eq!(mtree.proof_len(hash), Ok(mtree.depth()));
+                assert_eq!(mtree.proof_len(hash), Ok(mtree.generate_proofs(*hash).unwrap().steps.len()));
+            }
+            assert_eq!(mtree.proof_len(&Hash::hash(b"Bonjour")), Err(MerkleError::LeafNotFound));
+        }
+    }
+
+    #[test]
+    fn test_verify_self_paired_leaf() {
+        let expected_hash = hex::decode("d31a37ef6ac14a2db1470c4316beb5592e6afd4465022339adafda76a18ffabe").unwrap();
+        let contents = ["a", "b", "c"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let proofs = mtree.generate_proofs(hashes[2]).unwrap().into_tuples();
+        assert_eq!(proofs[0], (hashes[2], 1));
+        assert_eq!(MerkleTree::verify(contents[2].as_bytes().to_vec(), proofs).to_vec(), expected_hash);
+
+        for count in (1..16u8).step_by(2) {
+            let hashes: Vec<[u8; 32]> = (0..count).map(|i| Hash::hash(&[i])).collect();
+            let mtree = MerkleTree::new(hashes);
+
+            for index in 0..count {
+                let proofs = mtree.generate_proof_by_index(index as usize).unwrap();
+                assert_eq!(&MerkleTree::verify(vec![index], proofs), mtree.root_hash());
+            }
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let builder = MerkleTreeBuilder::new().sorted_pairs(true).domain_separation(true);
+        let verifier = builder.verifier();
+
+        let contents = ["Hello", "Hi", "Hey", "Hola", "Bonjour"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| verifier.hash_leaf(data.as_bytes())).collect();
+        let mtree = builder.build(hashes.clone()).unwrap();
+
+        for (index, hash) in hashes.iter().enumerate() {
+            let mut proofs = mtree.generate_proof_by_index(index).unwrap();
+            assert!(verifier.verify(*hash, &proofs, mtree.root_hash()));
+            assert!(mtree.verifier().verify(*hash, &proofs, mtree.root_hash()));
+
+            // sorted pairs don't care about directions
+            proofs.iter_mut().for_each(|(_, direction)| *direction ^= 1);
+            assert!(verifier.verify(*hash, &proofs, mtree.root_hash()));
+            assert!(!MerkleTreeBuilder::new().verifier().verify(*hash, &proofs, mtree.root_hash()));
+        }
+
+        assert_ne!(mtree.root_hash(), MerkleTree::new_sorted(hashes.clone()).root_hash());
+        assert_ne!(mtree.root_hash(), MerkleTree::new_with_domain_separation(hashes.clone()).root_hash());
+
+        let mtree = MerkleTreeBuilder::new().hasher::<DoubleSha256Hasher>().build(hashes.clone()).unwrap();
+        assert_eq!(mtree.root_hash(), GenericMerkleTree::<DoubleSha256Hasher>::new(hashes).root_hash());
+
+        assert_eq!(MerkleTreeBuilder::new().build(vec![]).err(), Some(MerkleError::EmptyTree));
+        assert_eq!(MerkleTreeBuilder::new().truncate(0).build(vec![[0u8; 32]]).err(), Some(MerkleError::InvalidTruncation));
+    }
+
+    #[test]
+    fn test_leaves_iter() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        assert!(mtree.leaves().all(|n| matches!(n, Node::Leaf { .. })));
+        assert_eq!(mtree.leaves().map(|n| *n.hash().unwrap()).collect::<Vec<[u8; 32]>>(), hashes);
+        assert_eq!(mtree.leaf_hashes_iter().copied().collect::<Vec<[u8; 32]>>(), hashes);
+    }
+
+    #[test]
+    fn test_short_hasher() {
+        let short = |data: &[u8]| Hash::hash(data)[..20].to_vec();
+
+        let contents = ["Hello", "Hi", "Hey"];
+        let hashes: Vec<[u8; 20]> = contents.iter().map(|data| Sha256_160Hasher::hash(data.as_bytes())).collect();
+        let mtree = GenericMerkleTree::<Sha256_160Hasher, 20>::new(hashes.clone());
+
+        // a 20 byte tree only ever hashes 40 bytes at a time
+        let leaves: Vec<Vec<u8>> = contents.iter().map(|data| short(data.as_bytes())).collect();
+        let left = short(&[leaves[0].clone(), leaves[1].clone()].concat());
+        let right = short(&[leaves[2].clone(), leaves[2].clone()].concat());
+        let root = short(&[left, right].concat());
+
+        assert_eq!(&mtree.root_hash()[..], &root[..]);
+        assert_eq!(mtree.truncation(), 20);
+
+        let proofs = mtree.generate_proof_by_index(2).unwrap();
+        assert_eq!(proofs.len(), 2);
+        assert_eq!(&GenericMerkleTree::<Sha256_160Hasher, 20>::verify(contents[2].as_bytes().to_vec(), proofs), mtree.root_hash());
+
+        let truncated = GenericMerkleTree::<Sha256_160Hasher, 20>::new_truncated(hashes.clone(), 20).unwrap();
+        assert!(truncated == mtree);
+        assert_eq!(GenericMerkleTree::<Sha256_160Hasher, 20>::new_truncated(hashes, 32).err(), Some(MerkleError::InvalidTruncation));
+    }
+
+    #[test]
+    fn test_wide_hasher() {
+        let wide = |data: &[u8]| sha2::Sha512::digest(data).to_vec();
+
+        let contents = ["Hello", "Hi", "Hey", "Hola", "Bonjour"];
+        let hashes: Vec<[u8; 64]> = contents.iter().map(|data| Sha512Hasher::hash(data.as_bytes())).collect();
+        let mtree = GenericMerkleTree::<Sha512Hasher, 64>::new(hashes.clone());
+
+        // every node is the whole 64 byte digest of its two children
+        let pair = |l: &[u8], r: &[u8]| wide(&[l, r].concat());
+        let l01 = pair(&hashes[0], &hashes[1]);
+        let l23 = pair(&hashes[2], &hashes[3]);
+        let l44 = pair(&hashes[4], &hashes[4]);
+        let root = pair(&pair(&l01, &l23), &pair(&l44, &l44));
+        assert_eq!(&mtree.root_hash()[..], &root[..]);
+
+        for (i, data) in contents.iter().enumerate() {
+            let proofs = mtree.generate_proof_by_index(i).unwrap();
+            assert_eq!(&GenericMerkleTree::<Sha512Hasher, 64>::verify(data.as_bytes().to_vec(), proofs.clone()), mtree.root_hash());
+            assert!(mtree.verifier().verify(hashes[i], &proofs, mtree.root_hash()));
+        }
+
+        let multiproof = mtree.generate_multiproof(&[hashes[1], hashes[4]]).unwrap();
+        assert!(multiproof.verify_with::<Sha512Hasher>(&[hashes[1], hashes[4]], mtree.root_hash()));
+
+        let proof = mtree.generate_proofs(hashes[3]).unwrap();
+        assert!(proof.verify_with::<Sha512Hasher>(mtree.root_hash()));
+
+        let sorted = MerkleTreeBuilder::<Sha512Hasher, 64>::default().sorted_pairs(true).build(hashes.clone()).unwrap();
+        let proofs = sorted.generate_proof_by_index(2).unwrap();
+        let siblings: Vec<[u8; 64]> = proofs.iter().map(|(sibling, _)| *sibling).collect();
+        assert!(GenericMerkleTree::<Sha512Hasher, 64>::verify_sorted(hashes[2], &siblings, sorted.root_hash()));
+
+        let truncated = GenericMerkleTree::<Sha512Hasher, 64>::new_truncated(hashes.clone(), 48).unwrap();
+        assert_eq!(truncated.root_hash()[48..], [0u8; 16]);
+        assert!(GenericMerkleTree::<Sha512Hasher, 64>::new_truncated(hashes.clone(), 64).unwrap() == mtree);
+        assert_eq!(GenericMerkleTree::<Sha512Hasher, 64>::new_truncated(hashes, 65).err(), Some(MerkleError::InvalidTruncation));
+    }
+
+    #[test]
+    fn test_roots_equal() {
+        let root = Hash::hash(b"root");
+        assert!(roots_equal(&root, &root));
+
+        for i in [0, 17, 31] {
+            let mut other = root;
+            other[i] ^= 0x80;
+            assert!(!roots_equal(&root, &other));
+        }
+        assert!(!roots_equal(&root, &root[..31]));
+
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proof_by_index(0).unwrap();
+
+        assert!(MerkleTree::verify_zerocopy(&hashes[0], &proofs, mtree.root_hash()));
+        assert!(!MerkleTree::verify_zerocopy(&hashes[0], &proofs, &root));
+    }
+
+    #[test]
+    fn test_proof_json() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let proofs = mtree.generate_proof_by_index(4).unwrap();
+
+        let json = proof_to_json(&proofs);
+        assert!(json.starts_with(&format!("[{{\"sibling\":\"{}\",\"right\":true}}", hex::encode(hashes[4]))));
+        assert_eq!(proof_from_json(&json), Ok(proofs));
+
+        let short = format!("[{{\"sibling\":\"{}\",\"right\":false}}]", &hex::encode(hashes[0])[..62]);
+        assert_eq!(proof_from_json(&short), Err(MerkleError::MalformedProof));
+        assert_eq!(proof_from_json("[{\"sibling\":\"00\"}]"), Err(MerkleError::MalformedProof));
+    }
+
+    #[test]
+    fn test_empty_node_hash() {
+        assert_eq!(Node::<32>::Empty.hash(), None);
+        assert_eq!(Node::Leaf { hash: [1u8; 32] }.hash(), Some(&[1u8; 32]));
+
+        // the right child of a duplicated odd node is Empty, the tree doesn't hash it
+        let hashes: Vec<[u8; 32]> = (0..3u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes);
+        let right = mtree.root().get_right().unwrap();
+        assert!(right.get_right().is_none());
+        assert!(right.hash().is_some());
+
+        // an empty tree still reports an all zero root
+        assert_eq!(MerkleTree::new(vec![]).root().hash(), None);
+        assert_eq!(MerkleTree::new(vec![]).root_hash(), &[0u8; 32]);
+    }
+
+    #[test]
+    fn test_sibling_of() {
+        let leaf = |i: u8| Arc::new(Node::Leaf { hash: Hash::hash(&[i]) });
+        let (a, b, c) = (leaf(0), leaf(1), leaf(2));
+
+        let pair = Node::Node { hash: [1u8; 32], left: Arc::clone(&a), right: Arc::clone(&b) };
+        let (sibling, direction) = pair.sibling_of(a.hash().unwrap()).unwrap();
+        assert_eq!((sibling.hash(), direction), (b.hash(), 1));
+        let (sibling, direction) = pair.sibling_of(b.hash().unwrap()).unwrap();
+        assert_eq!((sibling.hash(), direction), (a.hash(), 0));
+        assert!(pair.sibling_of(c.hash().unwrap()).is_none());
+
+        let odd = Node::Node { hash: [2u8; 32], left: Arc::clone(&c), right: Arc::new(Node::Empty) };
+        let (sibling, direction) = odd.sibling_of(c.hash().unwrap()).unwrap();
+        assert_eq!((sibling.hash(), direction), (c.hash(), 1));
+
+        assert!(c.sibling_of(c.hash().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_verify_and_locate() {
+        let hashes: Vec<[u8; 32]> = (0..8u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        for (i, hash) in hashes.iter().enumerate() {
+            let proofs = mtree.generate_proof_by_index(i).unwrap();
+            assert_eq!(MerkleTree::verify_any_index(*hash, &proofs, mtree.root_hash()), Some(i));
+            assert_eq!(MerkleTree::verify_any_index(*hash, &proofs, &hashes[0]), None);
+        }
+    }
 
-        let nodes: Vec<Rc<Node>> = leaves.into_iter().map(|hash| {
-            Rc::new(Node::Leaf { hash, parent: RefCell::new(Weak::new()) })
-        }).collect();
+    #[test]
+    fn test_leaf_index_after_updates() {
+        let a = Hash::hash(b"a");
+        let b = Hash::hash(b"b");
+        let mut mtree = MerkleTree::new(vec![a, b, a, b]);
+        assert_eq!(mtree.leaf_index(&a), Some(0));
 
-        let mut tree = Self::build_tree(&nodes);
-        tree.leaves.extend(nodes);
+        mtree.update_leaf(a, b).unwrap();
+        assert_eq!(mtree.leaf_index(&a), Some(2));
+        assert_eq!(mtree.leaf_index(&b), Some(0));
 
-        return tree;
+        mtree.update_leaf(a, b).unwrap();
+        assert!(!mtree.contains(&a));
+
+        let c = Hash::hash(b"c");
+        mtree.push_leaf(c);
+        mtree.push_leaf(b);
+        assert_eq!(mtree.leaf_index(&c), Some(4));
+        assert_eq!(mtree.leaf_index(&b), Some(0));
     }
 
-    fn build_tree(items: &Vec<Rc<Node>>) -> Self {        
-        if items.len() == 1 {
-            return Self { root: Rc::clone(&items[0]), leaves: vec![] };
-        }
+    #[test]
+    fn test_merkle_proof_verify_data() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
 
-        let mut nodes: Vec<Rc<Node>> = vec![];
-        for i in (0..items.len()).step_by(2) {
-            let n: Rc<Node>;
-            if i+1 >= items.len() {
-                // if we have an odd number of nodes we duplicate the last one to calculate the hash
-                let hash = Hash::hash(&[items[i].hash().to_vec(), items[i].hash().to_vec()].concat());
-                let left = Rc::clone(&items[i]);
-                let right = Rc::new(Node::Empty);
+        let proof = mtree.generate_proofs(hashes[1]).unwrap();
+        assert_eq!(proof.verify_data(contents[1].as_bytes(), mtree.root_hash()), Ok(()));
+        assert_eq!(proof.verify_data(contents[2].as_bytes(), mtree.root_hash()), Err(MerkleError::LeafMismatch));
+        assert_eq!(proof.verify_data(contents[1].as_bytes(), &hashes[0]), Err(MerkleError::RootMismatch));
+    }
 
-                n = Rc::new(Node::Node { hash, parent: RefCell::new(Weak::new()), left, right });
+    #[test]
+    fn test_proof_by_index_duplicate_leaves() {
+        let contents = ["Hello", "Hi", "Hello", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
 
-                // update parent nodes
-                items[i].set_parent(&n);
-            } else {
-                let hash = Hash::hash(&[items[i].hash().to_vec(), items[i+1].hash().to_vec()].concat());
-                let left = Rc::clone(&items[i]);
-                let right = Rc::clone(&items[i+1]);
+        // by hash only the first "Hello" can be proven, by index both can
+        let first = mtree.generate_proof_by_index(0).unwrap();
+        let second = mtree.generate_proof_by_index(2).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(mtree.generate_proofs(hashes[2]).unwrap().into_tuples(), first);
 
-                n = Rc::new(Node::Node { hash, parent: RefCell::new(Weak::new()), left, right });
+        assert_eq!(MerkleTree::verify_any_index(hashes[0], &first, mtree.root_hash()), Some(0));
+        assert_eq!(MerkleTree::verify_any_index(hashes[2], &second, mtree.root_hash()), Some(2));
 
-                // update parent nodes
-                items[i].set_parent(&n);
-                items[i+1].set_parent(&n);
-            }
+        assert_eq!(mtree.generate_proof_by_index(4), Err(MerkleError::IndexOutOfBounds));
+    }
 
-            nodes.push(n);
-        }
+    #[test]
+    fn test_tree_eq() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
 
-        Self::build_tree(&nodes)
-    }
+        assert!(mtree == MerkleTree::new(hashes.clone()));
 
-    pub fn root_hash(&self) -> &[u8; 32] {
-        self.root.hash()
+        let mut swapped = hashes.clone();
+        swapped.swap(1, 2);
+        assert!(mtree != MerkleTree::new(swapped));
+        assert!(mtree != MerkleTree::new_sorted(hashes.clone()));
+        assert!(mtree != MerkleTree::new(hashes[..4].to_vec()));
     }
 
-    pub fn root(&self) -> &Node {
-        self.root.as_ref()
-    }
+    #[test]
+    fn test_proof_verifier() {
+        let contents = ["Hello", "Hi", "Hey", "Hola", "Bonjour"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
 
-    pub fn generate_proofs(&self, hash: [u8; 32]) -> Result<Vec<([u8;32], u8)>, Box<dyn Error + 'static>> {
-        // lookup for our leaf
-        let mut n: &Node = self.root();       
-        for l in &self.leaves {
-            if l.hash() == &hash {
-                n = &l;
-                break;
+        for (i, hash) in hashes.iter().enumerate() {
+            let proofs = mtree.generate_proof_by_index(i).unwrap();
+
+            let mut verifier = ProofVerifier::new(*hash);
+            for (sibling, direction) in &proofs {
+                verifier.push_step(*sibling, *direction == 1);
             }
+
+            let root = verifier.finalize();
+            assert_eq!(root, MerkleTree::verify(contents[i].as_bytes().to_vec(), proofs));
+            assert_eq!(&root, mtree.root_hash());
         }
 
-        let leaf_proof: Vec<([u8;32], u8)> = vec![];
-        let proofs = Self::gen_proof(&n, leaf_proof);
+        let mut verifier = ProofVerifier::<DoubleSha256Hasher>::with_hasher(hashes[0]);
+        verifier.push_step(hashes[1], true);
+        assert_eq!(verifier.finalize(), DoubleSha256Hasher::hash(&[hashes[0], hashes[1]].concat()));
+    }
 
-        Ok(proofs)
+    #[test]
+    fn test_levels() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let levels = mtree.levels();
+        assert_eq!(levels.iter().map(Vec::len).collect::<Vec<usize>>(), [4, 2, 1]);
+        assert_eq!(levels[0], hashes);
+        assert_eq!(levels[1][0], Hash::hash(&[hashes[0], hashes[1]].concat()));
+        assert_eq!(&levels[2][0], mtree.root_hash());
+
+        assert!(MerkleTree::new(vec![]).levels().is_empty());
     }
 
-    fn gen_proof(n: &Node, proofs: Vec<([u8;32], u8)>) -> Vec<([u8;32], u8)> {
-        let mut new_proof: Vec<([u8; 32], u8)> = vec![];
-        if let None = n.parent() {
-            return proofs;
+    #[test]
+    fn test_try_build_any_count() {
+        assert_eq!(MerkleTree::try_new(vec![]).err(), Some(MerkleError::EmptyTree));
+
+        // small xorshift so the leaf counts are arbitrary but the test is reproducible
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut counts = vec![1, 2, 3];
+        for _ in 0..20 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            counts.push(1 + (state % 1000) as usize);
         }
 
-        if let Node::Node {hash, ..} | Node::Leaf {hash, ..} = n {
-            let p = n.parent().unwrap(); // unwrap here is not great neither but should work fine.
-            let pleft = p.get_left().unwrap(); // We should always have left
+        for count in counts {
+            let hashes: Vec<[u8; 32]> = (0..count as u32).map(|i| Hash::hash(&i.to_le_bytes())).collect();
+            let mtree = MerkleTree::try_new(hashes.clone()).unwrap();
+            assert_eq!(mtree.leaf_count(), count);
 
-            if hash == pleft.hash() {
-                // sibling is right then
-                let pright = p.get_right().unwrap_or(pleft); // If right is empty we duplicate left
-                new_proof.push((pright.hash().clone(), 1));
-            } else {
-                new_proof.push((pleft.hash().clone(), 0));
+            for (i, hash) in hashes.iter().enumerate() {
+                let proofs = mtree.generate_proof_by_index(i).unwrap();
+                assert!(MerkleTree::verify_zerocopy(hash, &proofs, mtree.root_hash()));
             }
-        
-            return Self::gen_proof(p.as_ref(), [proofs, new_proof].concat());
         }
-
-        return vec![];
     }
 
-    pub fn verify(data: Vec<u8>, proofs: Vec<([u8;32], u8)>) -> [u8; 32] {
-        let mut hash = Hash::hash(&data);
+    #[test]
+    fn test_encode_proof_compact() {
+        for count in [1u32, 2, 5, 300, 1000] {
+            let hashes: Vec<[u8; 32]> = (0..count).map(|i| Hash::hash(&i.to_le_bytes())).collect();
+            let mtree = MerkleTree::new(hashes);
 
-        for proof in proofs {
-            if proof.1 == 1 {
-                hash = Hash::hash(&[hash, proof.0].concat());
-            } else {
-                hash = Hash::hash(&[proof.0, hash].concat());
-            }
+            let proofs = mtree.generate_proof_by_index(count as usize - 1).unwrap();
+            let bytes = encode_proof_compact(&proofs);
+
+            assert_eq!(bytes.len(), proofs.len() * 32 + proofs.len().div_ceil(8));
+            assert_eq!(decode_proof_compact(&bytes), Ok(proofs));
         }
 
-        return hash;
+        // a depth 20 proof, 3 bitmap bytes instead of 20 direction bytes
+        let proofs: Vec<([u8; 32], u8)> = (0..20u8).map(|i| (Hash::hash(&[i]), i % 2)).collect();
+        assert_eq!(encode_proof(&proofs).len(), 660);
+        assert_eq!(encode_proof_compact(&proofs).len(), 643);
+
+        let mut bytes = encode_proof_compact(&proofs);
+        assert_eq!(decode_proof_compact(&bytes[..bytes.len() - 1]), Err(MerkleError::MalformedProof));
+        bytes[2] |= 0x80;
+        assert_eq!(decode_proof_compact(&bytes), Err(MerkleError::MalformedProof));
     }
-}
 
+    #[test]
+    fn test_clone() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
 
-#[derive(Debug, Clone)]
-pub enum Node {
-    Empty,
-    Node { 
-        hash: [u8; 32],
-        parent: RefCell<Weak<Node>>,
-        left: Rc<Node>,
-        right: Rc<Node>,
-    },
-    Leaf {
-        hash: [u8; 32],
-        parent: RefCell<Weak<Node>>,
-    },
-}
+        let mut cloned = mtree.clone();
+        let proofs = cloned.generate_proof_by_index(3).unwrap();
+        assert!(MerkleTree::verify_zerocopy(&hashes[3], &proofs, cloned.root_hash()));
 
-impl Node {
-    pub fn hash(&self) -> &[u8; 32] {
-        match self {
-            Node::Node { hash, ..} => hash,
-            Node::Leaf { hash, ..} => hash,
-            _ => &[0u8; 32],
-        }
+        // the clone shares nodes but updating it leaves the original alone
+        cloned.update_leaf(hashes[3], Hash::hash(b"Bonjour")).unwrap();
+        assert_ne!(cloned.root_hash(), mtree.root_hash());
+        assert!(mtree == MerkleTree::new(hashes));
     }
 
-    pub fn set_parent(&self, p: &Rc<Node>) {
-        match self {
-            Node::Node { parent, ..} => *parent.borrow_mut() = Rc::downgrade(p), // need to fix this unwrap because we can't set parrent on root.
-            Node::Leaf { parent, ..} => *parent.borrow_mut() = Rc::downgrade(p),
-            _ => panic!("Empty and root doesnt have a parent"),
-        };
+    #[test]
+    fn test_extend_leaves() {
+        let hashes: Vec<[u8; 32]> = (0..11u8).map(|i| Hash::hash(&[i])).collect();
+
+        let mut mtree = MerkleTree::new(hashes[..4].to_vec());
+        mtree.extend_leaves(hashes[4..8].to_vec());
+        assert!(mtree == MerkleTree::new(hashes[..8].to_vec()));
+
+        mtree.extend_leaves(hashes[8..].to_vec());
+        assert_eq!(mtree.root_hash(), MerkleTree::new(hashes).root_hash());
     }
 
-    pub fn get_left(&self) -> Option<&Self> {
-        match self {
-            Node::Node{ left, ..} => Some(left),
-            _ => None,
+    #[test]
+    fn test_path_hashes() {
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        for hash in &hashes {
+            let path = mtree.path_hashes(hash).unwrap();
+            assert_eq!(path.len(), mtree.depth() + 1);
+            assert_eq!(&path[0], hash);
+            assert_eq!(path.last(), Some(mtree.root_hash()));
         }
+
+        // each step up hashes the previous node with its sibling from the proof
+        let path = mtree.path_hashes(&hashes[4]).unwrap();
+        assert_eq!(path[1], Hash::hash(&[hashes[4], hashes[4]].concat()));
+
+        assert_eq!(mtree.path_hashes(&Hash::hash(b"Bonjour")), Err(MerkleError::LeafNotFound));
     }
 
-    pub fn get_right(&self) -> Option<&Self> {
-        match self {
-            Node::Node{ right, ..} => { if let Node::Empty = right.as_ref() { None } else { Some(right) }},
-            _ => None,
-        }
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize_on_drop() {
+        use super::wipe_nodes;
+
+        let leaf = |hash: [u8; 32]| Arc::new(Node::Leaf { hash });
+        let shared = leaf([8u8; 32]);
+        let mut root = Arc::new(Node::Node { hash: [9u8; 32], left: leaf([7u8; 32]), right: Arc::clone(&shared) });
+
+        // what a tree does with its root once it's the last owner
+        wipe_nodes(&mut root);
+        assert_eq!(root.hash(), Some(&[0u8; 32]));
+        assert_eq!(root.get_left().unwrap().hash(), Some(&[0u8; 32]));
+        assert_eq!(shared.hash(), Some(&[8u8; 32]));
+
+        // taking a node apart still compiles with the feature on
+        let Node::Node { left, .. } = Arc::try_unwrap(root).unwrap() else { unreachable!() };
+        assert_eq!(left.hash(), Some(&[0u8; 32]));
+
+        // nodes shared with a clone outlive the dropped tree
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let cloned = mtree.clone();
+        drop(mtree);
+        assert_eq!(cloned.root_hash(), MerkleTree::new(hashes.clone()).root_hash());
+        assert_eq!(cloned.leaf_hashes(), hashes);
     }
 
-    pub fn parent(&self) -> Option<Rc<Self>> {
-        match self {
-            Node::Node{ parent, ..} => parent.borrow().upgrade(),
-            Node::Leaf{ parent, ..} => parent.borrow().upgrade(),
-            _ => None,
-        }
+    #[test]
+    fn test_from_iter() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let collected: MerkleTree = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        assert!(collected == mtree);
+        assert!(MerkleTree::from_iter(hashes.clone()) == mtree);
+        assert_eq!(MerkleTree::try_from_iter(hashes).unwrap().root_hash(), mtree.root_hash());
+
+        assert_eq!(MerkleTree::try_from_iter(std::iter::empty()).err(), Some(MerkleError::EmptyTree));
     }
-}
-#[cfg(test)]
-mod tests {
-    use hmac_sha256::Hash;
 
-    use super::MerkleTree;
+    #[test]
+    fn test_verify_sorted_witness() {
+        let hashes: Vec<[u8; 32]> = ["a", "b", "c", "d", "e"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new_sorted(hashes.clone());
+        // regression value, sha256 over sha256("a".."e")
+        assert_eq!(hex::encode(mtree.root_hash()), "df947ef1b6dda4cb4ef081afd68f255104ccaab2661f2047d2f1a05c5440076f");
+
+        for (i, hash) in hashes.iter().enumerate() {
+            let witness: Vec<[u8; 32]> = mtree.generate_proof_by_index(i).unwrap().iter().map(|(sibling, _)| *sibling).collect();
+            assert!(MerkleTree::verify_sorted(*hash, &witness, mtree.root_hash()));
+        }
+
+        // "e" is paired with itself twice on its way up
+        let e = hashes[4];
+        let ee = Hash::hash(&[e, e].concat());
+        let witness = mtree.generate_proof_by_index(4).unwrap();
+        assert_eq!(witness[0].0, e);
+        assert_eq!(witness[1].0, ee);
+        assert!(MerkleTree::verify_sorted(e, &[e, ee, witness[2].0], mtree.root_hash()));
+        assert!(!MerkleTree::verify_sorted(e, &[ee, e, witness[2].0], mtree.root_hash()));
+
+        // the witness OpenZeppelin's standard tree gives for leaf 0, as `MerkleProof.verify` takes it
+        let leaves = oz_leaves();
+        let root = unhex32(OZ_ROOT);
+        let witness: Vec<[u8; 32]> = OZ_PROOF.iter().map(|sibling| unhex32(sibling)).collect();
+        assert!(GenericMerkleTree::<Keccak256Hasher>::verify_sorted(leaves[0], &witness, &root));
+        assert!(!GenericMerkleTree::<Keccak256Hasher>::verify_sorted(leaves[1], &witness, &root));
+        assert!(!GenericMerkleTree::<Keccak256Hasher>::verify_sorted(leaves[0], &witness[..2], &root));
+    }
 
     #[test]
-    fn test_merkle_root() {
-        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
-        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
+    fn test_range_proof() {
+        let hashes: Vec<[u8; 32]> = (0..8u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
 
-        let mut hashes: Vec<[u8; 32]> = vec![];
-        for data in contents {
-            let hash = Hash::hash(data.as_bytes());
-            hashes.push(hash);
+        let proof = mtree.range_proof(2, 6).unwrap();
+        assert!(proof.verify(&hashes[2..6], mtree.root_hash()));
+        assert!(!proof.verify(&hashes[1..5], mtree.root_hash()));
+        assert!(!proof.verify(&hashes[2..5], mtree.root_hash()));
+
+        // the two subtrees bordering the range, (0, 1) and (6, 7)
+        let singles: usize = (2..6).map(|i| mtree.generate_proof_by_index(i).unwrap().len()).sum();
+        assert_eq!(proof.size(), 2);
+        assert!(proof.size() < singles);
+
+        // the last leaf of an odd tree is proven against its duplicate
+        let mtree = MerkleTree::new(hashes[..5].to_vec());
+        assert!(mtree.range_proof(3, 5).unwrap().verify(&hashes[3..5], mtree.root_hash()));
+
+        assert_eq!(mtree.range_proof(3, 3), Err(MerkleError::IndexOutOfBounds));
+        assert_eq!(mtree.range_proof(3, 6), Err(MerkleError::IndexOutOfBounds));
+    }
+
+    #[test]
+    fn test_from_items_with() {
+        struct Transfer {
+            to: &'static str,
+            amount: u64,
         }
 
-        let mtree = MerkleTree::new(hashes);
+        let transfers = vec![
+            Transfer { to: "alice", amount: 100 },
+            Transfer { to: "bob", amount: 42 },
+            Transfer { to: "carol", amount: 7 },
+        ];
+        let hash_transfer = |t: &Transfer| Hash::hash(&[t.to.as_bytes(), &t.amount.to_be_bytes()].concat());
+        let hashes: Vec<[u8; 32]> = transfers.iter().map(hash_transfer).collect();
 
-        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+        let mtree = MerkleTree::from_items_with(transfers, hash_transfer);
+        assert_eq!(mtree.leaf_hashes(), hashes);
+
+        let ab = Hash::hash(&[hashes[0], hashes[1]].concat());
+        let cc = Hash::hash(&[hashes[2], hashes[2]].concat());
+        assert_eq!(mtree.root_hash(), &Hash::hash(&[ab, cc].concat()));
     }
 
     #[test]
-    fn test_proofs_0() {
-        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
-        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
-        let mut hashes: Vec<[u8; 32]> = vec![];
-        for data in &contents {
-            let hash = Hash::hash(data.as_bytes());
-            hashes.push(hash);
+    fn test_generate_proof_refs() {
+        let hashes: Vec<[u8; 32]> = (0..7u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        for hash in &hashes {
+            let borrowed = mtree.generate_proof_refs(*hash).unwrap();
+            let owned = mtree.generate_proofs(*hash).unwrap().into_tuples();
+            assert_eq!(borrowed.into_iter().map(|(sibling, direction)| (*sibling, direction)).collect::<Vec<_>>(), owned);
         }
 
-        let first = hashes.first().unwrap().clone();
+        assert_eq!(mtree.generate_proof_refs(Hash::hash(b"Bonjour")), Err(MerkleError::LeafNotFound));
+    }
 
-        let mtree = MerkleTree::new(hashes);
+    #[test]
+    fn test_single_leaf_tree() {
+        let hash = Hash::hash(b"Hello");
+        let mut mtree = MerkleTree::new(vec![hash]);
 
-        let proofs = mtree.generate_proofs(first).unwrap();
-        let expected_root = mtree.root_hash();
+        // the leaf is the root, there is nothing to prove it against
+        assert_eq!(mtree.root_hash(), &hash);
+        assert_eq!(mtree.leaf_count(), 1);
+        assert_eq!(mtree.depth(), 0);
 
-        assert_eq!(expected_root.to_vec(), expected_hash);
+        let proof = mtree.generate_proofs(hash).unwrap();
+        assert!(proof.steps.is_empty());
+        assert!(proof.verify(mtree.root_hash()));
+        assert_eq!(MerkleTree::verify(b"Hello".to_vec(), proof.into_tuples()), hash);
+        assert_eq!(mtree.generate_proof_by_index(1), Err(MerkleError::IndexOutOfBounds));
 
-        let root = MerkleTree::verify(contents[0].as_bytes().to_vec(), proofs);
+        // growing it turns the leaf back into a proper leaf under a new root
+        let other = Hash::hash(b"Hi");
+        mtree.push_leaf(other);
+        assert_eq!(mtree.root_hash(), MerkleTree::new(vec![hash, other]).root_hash());
+        assert_eq!(mtree.generate_proof_by_index(0).unwrap(), [(other, 1)]);
+    }
 
-        assert_eq!(&root, expected_root);
+    #[test]
+    fn test_diff() {
+        let hashes: Vec<[u8; 32]> = (0..8u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+
+        let mut changed = hashes.clone();
+        changed[1] = Hash::hash(b"Hello");
+        changed[5] = Hash::hash(b"Hi");
+        let other = MerkleTree::new(changed);
+
+        assert_eq!(mtree.diff(&other).unwrap(), [1, 5]);
+        assert_eq!(other.diff(&mtree).unwrap(), [1, 5]);
+        assert!(mtree.diff(&mtree.clone()).unwrap().is_empty());
+
+        // a duplicated odd leaf gives the same root, the extra leaf still shows up
+        let three = MerkleTree::new(hashes[..3].to_vec());
+        let four = MerkleTree::new([&hashes[..3], &hashes[2..3]].concat());
+        assert_eq!(three.root_hash(), four.root_hash());
+        assert_eq!(three.diff(&four).unwrap(), [3]);
+
+        assert_eq!(mtree.diff(&MerkleTree::new(hashes[..3].to_vec())).unwrap(), [3, 4, 5, 6, 7]);
+
+        // different depths fall back to comparing leaf by leaf
+        let shorter = MerkleTree::new([&[Hash::hash(b"Hey")], &hashes[1..4]].concat());
+        assert_eq!(mtree.diff(&shorter).unwrap(), [0, 4, 5, 6, 7]);
+
+        // the same leaves hashed another way give another root
+        let promote = MerkleTreeBuilder::new().odd_node(OddNodeStrategy::Promote);
+        let promoted = promote.build(hashes[..3].to_vec()).unwrap();
+        assert_ne!(promoted.root_hash(), three.root_hash());
+        assert_eq!(three.diff(&promoted), Err(MerkleError::ConfigMismatch));
+
+        // a promoted leaf sits where the other tree has a subtree
+        let mut changed = hashes[..6].to_vec();
+        changed[4] = Hash::hash(b"Hola");
+        let five = promote.build(hashes[..5].to_vec()).unwrap();
+        assert_eq!(five.diff(&promote.build(changed.clone()).unwrap()).unwrap(), [4, 5]);
+        assert_eq!(five.diff(&promote.build(hashes[..6].to_vec()).unwrap()).unwrap(), [5]);
     }
 
+    #[test]
+    fn test_root_hex() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let mtree = MerkleTree::new(hashes);
+
+        let root_hex = "5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc";
+        assert_eq!(mtree.root_hex(), root_hex);
+
+        let proofs = mtree.generate_proof_by_index(2).unwrap();
+        assert_eq!(MerkleTree::verify_root_hex(b"Hey", &proofs, root_hex), Ok(true));
+        assert_eq!(MerkleTree::verify_root_hex(b"Hi", &proofs, root_hex), Ok(false));
+        assert_eq!(MerkleTree::verify_root_hex(b"Hey", &proofs, "5f30"), Err(MerkleError::InvalidHex));
+    }
 
     #[test]
-    fn test_proofs_1() {
-        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
-        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
-        let mut hashes: Vec<[u8; 32]> = vec![];
-        for data in &contents {
-            let hash = Hash::hash(data.as_bytes());
-            hashes.push(hash);
-        }
+    fn test_combined_proof() {
+        let hashes: Vec<[u8; 32]> = (0..8u8).map(|i| Hash::hash(&[i])).collect();
+        let subtrees = [MerkleTree::new(hashes[..4].to_vec()), MerkleTree::new(hashes[4..].to_vec())];
+        let global = MerkleTree::of_subtrees(&subtrees);
+
+        // two subtrees of 4 leaves make up the same root as a single tree of 8
+        assert_eq!(global.root_hash(), MerkleTree::new(hashes.clone()).root_hash());
+
+        let proofs = global.combined_proof(&subtrees[1], 1, hashes[6]).unwrap();
+        assert_eq!(proofs.len(), 3);
+        assert_eq!(&MerkleTree::verify(vec![6], proofs), global.root_hash());
 
-        let second = hashes[1].clone();
+        assert_eq!(global.combined_proof(&subtrees[0], 1, hashes[6]), Err(MerkleError::RootMismatch));
+        assert_eq!(global.combined_proof(&subtrees[1], 1, hashes[2]), Err(MerkleError::LeafNotFound));
+        assert_eq!(global.combined_proof(&subtrees[1], 2, hashes[6]), Err(MerkleError::IndexOutOfBounds));
+    }
 
+    #[test]
+    fn test_try_verify() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
         let mtree = MerkleTree::new(hashes);
 
-        let proofs = mtree.generate_proofs(second).unwrap();
-        let expected_root = mtree.root_hash();
+        let mut proofs = mtree.generate_proof_by_index(1).unwrap();
+        assert_eq!(MerkleTree::try_verify(b"Hi".to_vec(), proofs.clone()), Ok(*mtree.root_hash()));
 
-        assert_eq!(expected_root.to_vec(), expected_hash);
+        proofs[1].1 = 2;
+        assert_eq!(MerkleTree::try_verify(b"Hi".to_vec(), proofs), Err(MerkleError::MalformedProof));
+    }
 
-        let root = MerkleTree::verify(contents[1].as_bytes().to_vec(), proofs);
+    thread_local! {
+        static HASH_CALLS: Cell<usize> = const { Cell::new(0) };
+    }
 
-        assert_eq!(&root, expected_root);
+    struct CountingHasher;
+
+    impl MerkleHasher for CountingHasher {
+        const ALGORITHM_ID: &'static str = "sha256";
+
+        fn hash(data: &[u8]) -> [u8; 32] {
+            HASH_CALLS.with(|calls| calls.set(calls.get() + 1));
+            Hash::hash(data)
+        }
     }
 
     #[test]
-    fn test_proofs_2() {
-        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
-        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
-        let mut hashes: Vec<[u8; 32]> = vec![];
-        for data in &contents {
-            let hash = Hash::hash(data.as_bytes());
-            hashes.push(hash);
+    fn test_construction_hash_count() {
+        assert_eq!(construction_hash_count(0), 0);
+        assert_eq!(construction_hash_count(1), 0);
+        assert_eq!(construction_hash_count(4), 3);
+        assert_eq!(construction_hash_count(5), 6);
+        assert_eq!(construction_hash_count(6), 6);
+
+        for count in 1..=20u8 {
+            let hashes: Vec<[u8; 32]> = (0..count).map(|i| Hash::hash(&[i])).collect();
+            HASH_CALLS.with(|calls| calls.set(0));
+            let _ = GenericMerkleTree::<CountingHasher>::new(hashes);
+            assert_eq!(HASH_CALLS.with(Cell::get), construction_hash_count(count as usize));
         }
+    }
 
-        let third = hashes[2].clone();
+    #[test]
+    fn test_odd_node_strategy() {
+        let hashes: Vec<[u8; 32]> = ["a", "b", "c"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let ab = Hash::hash(&[hashes[0], hashes[1]].concat());
 
-        let mtree = MerkleTree::new(hashes);
+        let duplicate = MerkleTree::new(hashes.clone());
+        let promote = MerkleTreeBuilder::new().odd_node(OddNodeStrategy::Promote).build(hashes.clone()).unwrap();
+        assert_eq!(duplicate.odd_node(), OddNodeStrategy::Duplicate);
+        assert_eq!(promote.odd_node(), OddNodeStrategy::Promote);
 
-        let proofs = mtree.generate_proofs(third).unwrap();
-        let expected_root = mtree.root_hash();
+        let cc = Hash::hash(&[hashes[2], hashes[2]].concat());
+        assert_eq!(duplicate.root_hash(), &Hash::hash(&[ab, cc].concat()));
+        // c is carried up as is
+        assert_eq!(promote.root_hash(), &Hash::hash(&[ab, hashes[2]].concat()));
 
-        assert_eq!(expected_root.to_vec(), expected_hash);
+        for mtree in [&duplicate, &promote] {
+            for (i, hash) in hashes.iter().enumerate() {
+                let proofs = mtree.generate_proof_by_index(i).unwrap();
+                assert!(mtree.verifier().verify(*hash, &proofs, mtree.root_hash()));
+                assert_eq!(&MerkleTree::verify(["a", "b", "c"][i].as_bytes().to_vec(), proofs), mtree.root_hash());
+            }
+        }
+        assert_eq!(promote.generate_proof_by_index(2).unwrap(), [(ab, 0)]);
 
-        let root = MerkleTree::verify(contents[2].as_bytes().to_vec(), proofs);
+        // the promoted counterpart of the five leaf vector in test_root_other_set_2
+        let five: Vec<[u8; 32]> = ["a", "b", "c", "d", "e"].iter().map(|data| Hash::hash(data.as_bytes())).collect();
+        let promote = MerkleTreeBuilder::new().odd_node(OddNodeStrategy::Promote).build(five).unwrap();
+        assert_eq!(promote.root_hex(), "d71f8983ad4ee170f8129f1ebcdd7440be7798d8e1c80420bf11f1eced610dba");
 
-        assert_eq!(&root, expected_root);
+        // growing a promoted tree agrees with building it in one go
+        let mut grown = MerkleTreeBuilder::new().odd_node(OddNodeStrategy::Promote).build(hashes[..1].to_vec()).unwrap();
+        for count in 2..=7u8 {
+            grown.push_leaf(Hash::hash(&[count]));
+            let leaves = grown.leaf_hashes();
+            let built = MerkleTreeBuilder::new().odd_node(OddNodeStrategy::Promote).build(leaves.clone()).unwrap();
+            assert_eq!(grown.root_hash(), built.root_hash());
+            for (i, hash) in leaves.iter().enumerate() {
+                assert!(built.verifier().verify(*hash, &built.generate_proof_by_index(i).unwrap(), built.root_hash()));
+            }
+        }
     }
 
     #[test]
-    fn test_proofs_3() {
-        let expected_hash = hex::decode("5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc").unwrap();
-        let contents = vec!["Hello", "Hi", "Hey", "Hola"];
-        let mut hashes: Vec<[u8; 32]> = vec![];
-        for data in &contents {
-            let hash = Hash::hash(data.as_bytes());
-            hashes.push(hash);
+    fn test_verify_by_index() {
+        for count in [8u8, 5] {
+            let hashes: Vec<[u8; 32]> = (0..count).map(|i| Hash::hash(&[i])).collect();
+            let mtree = MerkleTree::new(hashes.clone());
+            let root = mtree.root_hash();
+            let total = count as usize;
+
+            for (i, hash) in hashes.iter().enumerate() {
+                let siblings: Vec<[u8; 32]> = mtree.generate_proof_by_index(i).unwrap().iter().map(|(s, _)| *s).collect();
+                assert!(MerkleTree::verify_by_index(*hash, i, &siblings, total, root));
+                assert!(!MerkleTree::verify_by_index(*hash, i ^ 1, &siblings, total, root));
+                assert!(!MerkleTree::verify_by_index(*hash, i, &siblings[1..], total, root));
+                assert!(!MerkleTree::verify_by_index(*hash, i, &siblings, total + 8, root));
+            }
         }
 
-        let last = hashes.last().unwrap().clone();
+        // the last of 5 leaves is paired with itself twice, those siblings have to be the running hash
+        let hashes: Vec<[u8; 32]> = (0..5u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTree::new(hashes.clone());
+        let mut siblings: Vec<[u8; 32]> = mtree.generate_proof_by_index(4).unwrap().iter().map(|(s, _)| *s).collect();
+        assert_eq!(siblings[0], hashes[4]);
+        siblings[0] = hashes[3];
+        assert!(!MerkleTree::verify_by_index(hashes[4], 4, &siblings, 5, mtree.root_hash()));
+    }
 
-        let mtree = MerkleTree::new(hashes);
+    #[test]
+    fn test_root_bytes() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
 
-        let proofs = mtree.generate_proofs(last).unwrap();
-        let expected_root = mtree.root_hash();
+        let root = {
+            let mtree = MerkleTree::new(hashes);
+            mtree.root_bytes()
+        };
 
-        assert_eq!(expected_root.to_vec(), expected_hash);
+        assert_eq!(hex::encode(root), "5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc");
+    }
 
-        let root = MerkleTree::verify(contents[3].as_bytes().to_vec(), proofs);
+    #[test]
+    fn test_consistency_proof() {
+        let hashes: Vec<[u8; 32]> = (0..13u8).map(|i| Hash::hash(&[i])).collect();
 
-        assert_eq!(&root, expected_root);
+        let old = MerkleTree::new(hashes[..4].to_vec());
+        let mut new = old.clone();
+        new.extend_leaves(hashes[4..6].to_vec());
+
+        let proof = new.consistency_proof(4).unwrap();
+        assert!(verify_consistency(old.root_hash(), new.root_hash(), 4, 6, &proof));
+        assert!(!verify_consistency(new.root_hash(), old.root_hash(), 4, 6, &proof));
+        assert!(!verify_consistency(old.root_hash(), new.root_hash(), 4, 9, &proof));
+
+        // every old size against every new one, odd sizes included
+        for new_size in 1..=hashes.len() {
+            let new = MerkleTree::new(hashes[..new_size].to_vec());
+            for old_size in 1..=new_size {
+                let old = MerkleTree::new(hashes[..old_size].to_vec());
+                let proof = new.consistency_proof(old_size).unwrap();
+                assert!(verify_consistency(old.root_hash(), new.root_hash(), old_size, new_size, &proof));
+            }
+        }
+
+        // an old tree that isn't a prefix of the new one
+        let mut forked = hashes[..5].to_vec();
+        forked[2] = Hash::hash(b"Hello");
+        let forked = MerkleTree::new(forked);
+        let new = MerkleTree::new(hashes[..9].to_vec());
+        assert!(!verify_consistency(forked.root_hash(), new.root_hash(), 5, 9, &new.consistency_proof(5).unwrap()));
+
+        assert_eq!(new.consistency_proof(0), Err(MerkleError::IndexOutOfBounds));
+        assert_eq!(new.consistency_proof(10), Err(MerkleError::IndexOutOfBounds));
     }
 
     #[test]
-    fn test_root_other_set() {
-        // Test from https://github.com/olivmath/merkly/blob/main/test/merkle_root/test_merkle_root.py#L99
-        let expected_hash = hex::decode("14ede5e8e97ad9372327728f5099b95604a39593cac3bd38a343ad76205213e7").unwrap();
-        let contents = vec!["a", "b", "c", "d"];
+    fn test_consistency_proof_with_config() {
+        let hashes: Vec<[u8; 32]> = (0..9u8).map(|i| Hash::hash(&[i])).collect();
+        let builder = MerkleTreeBuilder::new().domain_separation(true).sorted_pairs(true);
+        let verifier = builder.verifier();
 
-        let mut hashes: Vec<[u8; 32]> = vec![];
-        for data in contents {
-            let hash = Hash::hash(data.as_bytes());
-            hashes.push(hash);
+        for new_size in 1..=hashes.len() {
+            let new = builder.build(hashes[..new_size].to_vec()).unwrap();
+            for old_size in 1..=new_size {
+                let old = builder.build(hashes[..old_size].to_vec()).unwrap();
+                let proof = new.consistency_proof(old_size).unwrap();
+                assert!(verifier.verify_consistency(old.root_hash(), new.root_hash(), old_size, new_size, &proof));
+            }
         }
 
+        // plain pairing can't fold domain separated roots
+        let old = builder.build(hashes[..3].to_vec()).unwrap();
+        let new = builder.build(hashes.clone()).unwrap();
+        assert!(!verify_consistency(old.root_hash(), new.root_hash(), 3, 9, &new.consistency_proof(3).unwrap()));
+
+        let old = GenericMerkleTree::<DoubleSha256Hasher>::new(hashes[..5].to_vec());
+        let new = GenericMerkleTree::<DoubleSha256Hasher>::new(hashes.clone());
+        assert!(new.verifier().verify_consistency(old.root_hash(), new.root_hash(), 5, 9, &new.consistency_proof(5).unwrap()));
+    }
+
+    #[test]
+    fn test_verify_iter() {
+        let hashes: Vec<[u8; 32]> = (0..7u8).map(|i| Hash::hash(&[i])).collect();
         let mtree = MerkleTree::new(hashes);
 
-        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+        for i in 0..7u8 {
+            let proofs = mtree.generate_proof_by_index(i as usize).unwrap();
+            let bytes = encode_proof(&proofs);
+
+            // steps decoded on the fly from the encoded proof
+            let steps = bytes.chunks_exact(PROOF_STEP_SIZE).map(|step| (step[..32].try_into().unwrap(), step[32]));
+            assert_eq!(MerkleTree::verify_iter(&[i], steps), MerkleTree::verify(vec![i], proofs));
+        }
     }
 
     #[test]
-    fn test_root_other_set_1() {
-        let expected_hash = hex::decode("e5a01fee14e0ed5c48714f22180f25ad8365b53f9779f79dc4a3d7e93963f94a").unwrap();
-        let contents = vec!["a", "b"];
+    fn test_new_cached() {
+        let mut hashes: Vec<[u8; 32]> = (0..100u8).map(|i| Hash::hash(&[i])).collect();
+        let mut cache = MerkleTreeCache::new();
 
-        let mut hashes: Vec<[u8; 32]> = vec![];
-        for data in contents {
-            let hash = Hash::hash(data.as_bytes());
-            hashes.push(hash);
+        for count in [1, 2, 5, 8, 100] {
+            let cached = MerkleTree::new_cached(hashes[..count].to_vec(), &mut cache);
+            assert_eq!(cached.root_hash(), MerkleTree::new(hashes[..count].to_vec()).root_hash());
         }
 
+        // a second build with one leaf changed only hashes that leaf's path
+        let mut cache = MerkleTreeCache::<CountingHasher>::new();
+        let _ = GenericMerkleTree::<CountingHasher>::new_cached(hashes.clone(), &mut cache);
+        hashes[42] = Hash::hash(b"Hello");
+        HASH_CALLS.with(|calls| calls.set(0));
+        let cached = GenericMerkleTree::<CountingHasher>::new_cached(hashes.clone(), &mut cache);
+        assert_eq!(HASH_CALLS.with(Cell::get), cached.depth());
+        assert_eq!(cached.root_hash(), MerkleTree::new(hashes).root_hash());
+    }
+
+    #[test]
+    fn test_verify_with_info() {
+        let contents = ["Hello", "Hi", "Hey", "Hola"];
+        let hashes: Vec<[u8; 32]> = contents.iter().map(|data| Hash::hash(data.as_bytes())).collect();
         let mtree = MerkleTree::new(hashes);
-        
-        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+
+        let proofs = mtree.generate_proof_by_index(3).unwrap();
+        let result = MerkleTree::verify_with_info(b"Hola".to_vec(), proofs.clone());
+        assert_eq!(result, VerifyResult { computed_root: *mtree.root_hash(), steps_applied: proofs.len() });
+        assert_eq!(result.steps_applied, 2);
     }
 
-    // #[test]
-    // fn test_root_other_set_2() {
-    //     let expected_hash = hex::decode("d71f8983ad4ee170f8129f1ebcdd7440be7798d8e1c80420bf11f1eced610dba").unwrap();
-    //     let contents = vec!["a", "b", "c", "d", "e"];
+    #[test]
+    fn test_try_from_vecs() {
+        let hashes: Vec<[u8; 32]> = (0..4u8).map(|i| Hash::hash(&[i])).collect();
+        let mut leaves: Vec<Vec<u8>> = hashes.iter().map(|hash| hash.to_vec()).collect();
 
-    //     let mut hashes: Vec<[u8; 32]> = vec![];
-    //     for data in contents {
-    //         let hash = Hash::hash(data.as_bytes());
-    //         hashes.push(hash);
-    //     }
+        let mtree = MerkleTree::try_from_vecs(leaves.clone()).unwrap();
+        assert_eq!(mtree.root_hash(), MerkleTree::new(hashes).root_hash());
 
-    //     let mtree = MerkleTree::new(hashes);
-        
-    //     assert_eq!(mtree.root_hash().to_vec(), expected_hash);
-    // }
+        leaves[2].pop();
+        let err = MerkleTree::try_from_vecs(leaves).err().unwrap();
+        assert_eq!(err, MerkleError::BadLeafLength { index: 2, len: 31 });
+        assert_eq!(err.to_string(), "leaf 2 is 31 bytes long, not a hash");
+
+        // 20 byte hashers take 20 byte leaves
+        let short: Vec<Vec<u8>> = (0..3u8).map(|i| Sha256_160Hasher::hash(&[i]).to_vec()).collect();
+        assert!(GenericMerkleTree::<Sha256_160Hasher, 20>::try_from_vecs(short.clone()).is_ok());
+        assert!(MerkleTree::try_from_vecs(short).is_err());
+
+        assert_eq!(MerkleTree::try_from_vecs(vec![]).err(), Some(MerkleError::EmptyTree));
+    }
 
     #[test]
-    fn test_root_other_set_3() {
-        // test from https://github.com/merkletreejs/merkletreejs/blob/master/test/MerkleTree.test.js#L188
-        let expected_hash = hex::decode("44205acec5156114821f1f71d87c72e0de395633cd1589def6d4444cc79f8103").unwrap();
-        let contents = vec!["a", "b", "c", "d", "e", "f"];
+    fn test_multiproof_tree_config() {
+        let hashes: Vec<[u8; 32]> = (0..7u8).map(|i| Hash::hash(&[i])).collect();
+        let proven = [hashes[1], hashes[4], hashes[6]];
 
-        let mut hashes: Vec<[u8; 32]> = vec![];
-        for data in contents {
-            let hash = Hash::hash(data.as_bytes());
-            hashes.push(hash);
+        let trees = [
+            MerkleTree::new_with_domain_separation(hashes.clone()),
+            MerkleTree::new_truncated(hashes.clone(), 16).unwrap(),
+            MerkleTree::new_sorted(hashes.clone()),
+        ];
+        for mtree in &trees {
+            assert!(mtree.generate_multiproof(&proven).unwrap().verify(&proven, mtree.root_hash()));
+            assert!(mtree.range_proof(2, 6).unwrap().verify(&hashes[2..6], mtree.root_hash()));
         }
 
-        let mtree = MerkleTree::new(hashes);
-        
-        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+        let mtree = GenericMerkleTree::<DoubleSha256Hasher>::new(hashes.clone());
+        let multiproof = mtree.generate_multiproof(&proven).unwrap();
+        assert!(multiproof.verify_with::<DoubleSha256Hasher>(&proven, mtree.root_hash()));
+        assert!(!multiproof.verify(&proven, mtree.root_hash()));
+        assert!(mtree.range_proof(2, 6).unwrap().verify_with::<DoubleSha256Hasher>(&hashes[2..6], mtree.root_hash()));
     }
 
     #[test]
-    fn test_root_other_set_4() {
-        // test from https://github.com/merkletreejs/merkletreejs/blob/master/test/MerkleTree.test.js#L218
-        let expected_hash = hex::decode("d31a37ef6ac14a2db1470c4316beb5592e6afd4465022339adafda76a18ffabe").unwrap();
-        let contents = vec!["a", "b", "c"];
+    fn test_promote_unsupported_proofs() {
+        let hashes: Vec<[u8; 32]> = (0..3u8).map(|i| Hash::hash(&[i])).collect();
+        let mtree = MerkleTreeBuilder::new().odd_node(OddNodeStrategy::Promote).build(hashes.clone()).unwrap();
 
-        let mut hashes: Vec<[u8; 32]> = vec![];
-        for data in contents {
-            let hash = Hash::hash(data.as_bytes());
-            hashes.push(hash);
+        assert_eq!(mtree.proof_len(&hashes[2]), Ok(1));
+        assert_eq!(mtree.proof_len(&hashes[0]), Ok(2));
+        for (i, hash) in hashes.iter().enumerate() {
+            assert_eq!(mtree.proof_len(hash), Ok(mtree.generate_proof_by_index(i).unwrap().len()));
         }
+        assert_eq!(mtree.affected_indices(2), [0, 1]);
 
-        let mtree = MerkleTree::new(hashes);
-        
-        assert_eq!(mtree.root_hash().to_vec(), expected_hash);
+        assert_eq!(mtree.generate_multiproof(&hashes[..2]), Err(MerkleError::UnsupportedOddNodeStrategy));
+        assert_eq!(mtree.range_proof(0, 2), Err(MerkleError::UnsupportedOddNodeStrategy));
+        assert_eq!(mtree.consistency_proof(2), Err(MerkleError::UnsupportedOddNodeStrategy));
     }
-}
\ No newline at end of file
+}
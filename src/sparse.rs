@@ -0,0 +1,144 @@
+use crate::merkle::{hash_pair, roots_equal, MerkleHasher, Sha256Hasher};
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// one level per bit of the key hash
+pub const SPARSE_DEPTH: usize = 256;
+
+// siblings from the leaf level up to just below the root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseProof {
+    pub siblings: Vec<[u8; 32]>,
+}
+
+pub struct SparseMerkleTree {
+    // non-default nodes keyed by (height, path with the lowest `height` bits cleared)
+    nodes: BTreeMap<(usize, [u8; 32]), [u8; 32]>,
+    defaults: Vec<[u8; 32]>,
+}
+
+impl Default for SparseMerkleTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SparseMerkleTree {
+    pub fn new() -> Self {
+        Self { nodes: BTreeMap::new(), defaults: Self::default_hashes() }
+    }
+
+    // defaults[h] is the hash of an empty subtree of height h, an empty leaf being all zeros
+    fn default_hashes() -> Vec<[u8; 32]> {
+        let mut defaults = vec![[0u8; 32]];
+        for h in 0..SPARSE_DEPTH {
+            defaults.push(hash_pair::<Sha256Hasher, 32>(&defaults[h], &defaults[h]));
+        }
+        defaults
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) {
+        let path = Sha256Hasher::hash(key);
+        let mut hash = Sha256Hasher::hash(value);
+        self.nodes.insert((0, path), hash);
+
+        for h in 0..SPARSE_DEPTH {
+            let sibling = self.node(h, &flip_bit(&path, SPARSE_DEPTH - 1 - h));
+            hash = if bit(&path, SPARSE_DEPTH - 1 - h) {
+                hash_pair::<Sha256Hasher, 32>(&sibling, &hash)
+            } else {
+                hash_pair::<Sha256Hasher, 32>(&hash, &sibling)
+            };
+            self.nodes.insert((h + 1, prefix(&path, h + 1)), hash);
+        }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.node(SPARSE_DEPTH, &[0u8; 32])
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&[u8; 32]> {
+        self.nodes.get(&(0, Sha256Hasher::hash(key)))
+    }
+
+    // the same proof shape serves membership and non-membership, it depends on the value we verify against
+    pub fn prove(&self, key: &[u8]) -> SparseProof {
+        let path = Sha256Hasher::hash(key);
+        let siblings = (0..SPARSE_DEPTH)
+            .map(|h| self.node(h, &flip_bit(&path, SPARSE_DEPTH - 1 - h)))
+            .collect();
+
+        SparseProof { siblings }
+    }
+
+    // `value` is None to check that `key` is absent from the tree
+    pub fn verify(root: &[u8; 32], key: &[u8], value: Option<&[u8]>, proof: &SparseProof) -> bool {
+        if proof.siblings.len() != SPARSE_DEPTH {
+            return false;
+        }
+
+        let path = Sha256Hasher::hash(key);
+        let mut hash = value.map(Sha256Hasher::hash).unwrap_or([0u8; 32]);
+        for (h, sibling) in proof.siblings.iter().enumerate() {
+            hash = if bit(&path, SPARSE_DEPTH - 1 - h) {
+                hash_pair::<Sha256Hasher, 32>(sibling, &hash)
+            } else {
+                hash_pair::<Sha256Hasher, 32>(&hash, sibling)
+            };
+        }
+
+        roots_equal(&hash, root)
+    }
+
+    fn node(&self, height: usize, path: &[u8; 32]) -> [u8; 32] {
+        *self.nodes.get(&(height, prefix(path, height))).unwrap_or(&self.defaults[height])
+    }
+}
+
+// bit 0 is the most significant bit, the one chosen right under the root
+fn bit(path: &[u8; 32], i: usize) -> bool {
+    (path[i / 8] >> (7 - i % 8)) & 1 == 1
+}
+
+fn flip_bit(path: &[u8; 32], i: usize) -> [u8; 32] {
+    let mut p = *path;
+    p[i / 8] ^= 1 << (7 - i % 8);
+    p
+}
+
+// keep the first `SPARSE_DEPTH - height` bits of the path, identifying a node at that height
+fn prefix(path: &[u8; 32], height: usize) -> [u8; 32] {
+    let mut p = *path;
+    for i in (SPARSE_DEPTH - height)..SPARSE_DEPTH {
+        p[i / 8] &= !(1 << (7 - i % 8));
+    }
+    p
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SparseMerkleTree;
+
+    #[test]
+    fn test_sparse_non_membership() {
+        let mut smt = SparseMerkleTree::new();
+        let empty_root = smt.root();
+        // 256 levels of sha256(empty || empty) over an all zero leaf
+        assert_eq!(hex::encode(empty_root), "b178c245c947ea7e21ecede07728941a6ab1b706143c06873baff8ebd6de6308");
+
+        smt.insert(b"alice", b"100");
+        smt.insert(b"bob", b"42");
+
+        let root = smt.root();
+        assert_ne!(root, empty_root);
+
+        let proof = smt.prove(b"alice");
+        assert!(SparseMerkleTree::verify(&root, b"alice", Some(b"100"), &proof));
+        assert!(!SparseMerkleTree::verify(&root, b"alice", None, &proof));
+
+        let proof = smt.prove(b"carol");
+        assert!(SparseMerkleTree::verify(&root, b"carol", None, &proof));
+        assert!(!SparseMerkleTree::verify(&root, b"carol", Some(b"7"), &proof));
+    }
+}
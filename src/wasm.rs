@@ -0,0 +1,73 @@
+use crate::merkle::{proof_to_merkletreejs_json, MerkleError, MerkleTree};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct WasmMerkleTree {
+    tree: MerkleTree,
+}
+
+#[wasm_bindgen]
+impl WasmMerkleTree {
+    // leaves are hex encoded hashes, with or without a 0x prefix
+    pub fn new_from_hex_leaves(leaves: Vec<String>) -> Result<WasmMerkleTree, JsError> {
+        Self::from_hex_leaves(&leaves).map_err(js_error)
+    }
+
+    pub fn root_hex(&self) -> String {
+        self.tree.root_hex()
+    }
+
+    // the proof as merkletreejs lays it out, a JSON string to `JSON.parse`
+    pub fn generate_proof_hex(&self, leaf_hex: &str) -> Result<JsValue, JsError> {
+        self.proof_json(leaf_hex).map(|json| JsValue::from_str(&json)).map_err(js_error)
+    }
+}
+
+// the JS types only work on wasm, everything else stays plain Rust so it can be tested natively
+impl WasmMerkleTree {
+    fn from_hex_leaves(leaves: &[String]) -> Result<Self, MerkleError> {
+        let hashes = leaves.iter().map(|leaf| parse_hash(leaf)).collect::<Result<Vec<[u8; 32]>, MerkleError>>()?;
+
+        Ok(Self { tree: MerkleTree::try_new(hashes)? })
+    }
+
+    fn proof_json(&self, leaf_hex: &str) -> Result<String, MerkleError> {
+        let proof = self.tree.generate_proofs(parse_hash(leaf_hex)?)?;
+
+        Ok(proof_to_merkletreejs_json(&proof.into_tuples()))
+    }
+}
+
+fn parse_hash(hex: &str) -> Result<[u8; 32], MerkleError> {
+    let mut hash = [0u8; 32];
+    hex::decode_to_slice(hex.strip_prefix("0x").unwrap_or(hex), &mut hash).map_err(|_| MerkleError::InvalidHex)?;
+
+    Ok(hash)
+}
+
+fn js_error(err: MerkleError) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WasmMerkleTree;
+    use crate::merkle::MerkleError;
+    use hmac_sha256::Hash;
+
+    #[test]
+    fn test_wasm_root_hex() {
+        let leaves: Vec<String> = ["Hello", "Hi", "Hey", "Hola"].iter().map(|data| hex::encode(Hash::hash(data.as_bytes()))).collect();
+
+        let tree = WasmMerkleTree::from_hex_leaves(&leaves).unwrap();
+        assert_eq!(tree.root_hex(), "5f30cc80133b9394156e24b233f0c4be32b24e44bb3381f02c7ba52619d0febc");
+
+        let prefixed: Vec<String> = leaves.iter().map(|leaf| format!("0x{}", leaf)).collect();
+        assert_eq!(WasmMerkleTree::from_hex_leaves(&prefixed).unwrap().root_hex(), tree.root_hex());
+
+        let json = tree.proof_json(&leaves[1]).unwrap();
+        assert!(json.starts_with("[{\"position\":\"left\""));
+        assert_eq!(tree.proof_json("00"), Err(MerkleError::InvalidHex));
+        assert!(WasmMerkleTree::from_hex_leaves(&[]).is_err());
+    }
+}